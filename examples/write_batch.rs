@@ -9,7 +9,7 @@ fn main() -> Result<(), Error> {
     let mut options = Options::new();
     options.create_if_missing = true;
 
-    let database = Database::open(&path, &options)?;
+    let database = Database::open(path, &options)?;
     let batch = WriteBatch::new();
     let write_ops = WriteOptions::new();
     let read_ops = ReadOptions::new();
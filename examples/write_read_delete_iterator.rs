@@ -10,7 +10,7 @@ fn main() -> Result<(), Error> {
     let mut options = Options::new();
     options.create_if_missing = true;
 
-    let database = Database::open(&path, &options)?;
+    let database = Database::open(path, &options)?;
 
     let write_ops = WriteOptions::new();
     let read_ops = ReadOptions::new();
@@ -68,7 +68,7 @@ fn main() -> Result<(), Error> {
     // iterator
     let iter = database.iter(&read_ops);
 
-    let mut key_and_values = vec![("name", "tom"), ("age", "5"), ("from", "mars")];
+    let mut key_and_values = [("name", "tom"), ("age", "5"), ("from", "mars")];
     key_and_values.sort();
 
     for entry in iter.enumerate() {
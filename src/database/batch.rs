@@ -1,10 +1,21 @@
+use super::bytes::raw_slice;
 use super::db::Database;
 use super::error::Error;
+use super::iterator::{Iterable, LevelDBIterator};
 use super::key::IntoLevelDBKey;
-use super::options::{c_writeoptions, WriteOptions};
+use super::options::{c_writeoptions, ReadOptions, WriteOptions};
 use leveldb_sys::*;
 use libc::{c_char, c_void, size_t};
-use std::{ptr, slice};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::ptr;
+
+// leveldb's batch representation starts with a sequence number and a count
+const BATCH_HEADER_SIZE: usize = 12;
+
+// the panic of an unchecked put or delete adding a duplicate key
+const DUPLICATE_UNCHECKED: &str =
+    "duplicate key added unchecked to a batch detecting conflicts, use the try_ variants";
 
 pub(crate) struct RawWriteBatch {
     pub(crate) ptr: *mut leveldb_writebatch_t,
@@ -20,6 +31,9 @@ impl Drop for RawWriteBatch {
 
 pub struct WriteBatch {
     pub(crate) write_batch: RawWriteBatch,
+    size: Cell<usize>,
+    // the keys added so far, only tracked with conflict detection
+    keys: Option<RefCell<HashSet<Vec<u8>>>>,
 }
 
 /// Batch access to the database
@@ -41,6 +55,8 @@ impl Batch for Database {
                 &mut error,
             );
 
+            leveldb_writeoptions_destroy(c_write_options);
+
             if error.is_null() {
                 Ok(())
             } else {
@@ -50,6 +66,99 @@ impl Batch for Database {
     }
 }
 
+impl Database {
+    /// Buffer writes, writing them as a batch every `flush_every` operations
+    ///
+    /// See `WriteBuffer`. Panics if `flush_every` is 0.
+    pub fn buffered_writer(&self, flush_every: usize) -> WriteBuffer<'_> {
+        assert!(flush_every != 0, "flush_every must not be zero");
+
+        WriteBuffer {
+            database: self,
+            batch: WriteBatch::new(),
+            options: WriteOptions::new(),
+            flush_every,
+            pending: 0,
+        }
+    }
+}
+
+/// A buffer collecting puts and deletes into a batch, see `Database::buffered_writer`
+///
+/// The batch is written once `flush_every` operations were buffered, when
+/// `flush` is called and when the buffer is dropped. Errors of the write on drop
+/// are ignored, call `flush` to see them. Reads don't see buffered writes until
+/// they are flushed.
+pub struct WriteBuffer<'a> {
+    database: &'a Database,
+    batch: WriteBatch,
+    options: WriteOptions,
+    flush_every: usize,
+    pending: usize,
+}
+
+impl<'a> WriteBuffer<'a> {
+    /// Set the options the buffered batches are written with
+    pub fn with_options(mut self, options: WriteOptions) -> WriteBuffer<'a> {
+        self.options = options;
+        self
+    }
+
+    /// The number of buffered operations not yet written
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Buffer a put operation
+    pub fn put(&mut self, key: &dyn IntoLevelDBKey, value: &[u8]) -> Result<(), Error> {
+        self.batch.put(key, value);
+        self.added()
+    }
+
+    pub fn put_u8(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.batch.put_u8(key, value);
+        self.added()
+    }
+
+    /// Buffer a delete operation
+    pub fn delete(&mut self, key: &dyn IntoLevelDBKey) -> Result<(), Error> {
+        self.batch.delete(key);
+        self.added()
+    }
+
+    pub fn delete_u8(&mut self, key: &[u8]) -> Result<(), Error> {
+        self.batch.delete_u8(key);
+        self.added()
+    }
+
+    /// Write the buffered operations to the database
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        self.database.write(&self.options, &self.batch)?;
+        self.batch.clear();
+        self.pending = 0;
+
+        Ok(())
+    }
+
+    fn added(&mut self) -> Result<(), Error> {
+        self.pending += 1;
+        if self.pending >= self.flush_every {
+            self.flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Drop for WriteBuffer<'_> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
 impl Default for WriteBatch {
     fn default() -> Self {
         Self::new()
@@ -61,15 +170,65 @@ impl WriteBatch {
         let ptr = unsafe { leveldb_writebatch_create() };
         let raw = RawWriteBatch { ptr };
 
-        WriteBatch { write_batch: raw }
+        WriteBatch {
+            write_batch: raw,
+            size: Cell::new(BATCH_HEADER_SIZE),
+            keys: None,
+        }
+    }
+
+    /// Create a batch detecting keys that are added twice
+    ///
+    /// The keys of all operations are tracked, which costs a copy of each key.
+    /// `try_put` and `try_delete` and their variants then fail with an error
+    /// (see `Error::is_duplicate_key`) for a key already in the batch, instead
+    /// of letting the later operation silently win.
+    ///
+    /// # Panics
+    ///
+    /// `put` and `delete` and their variants can't report an error, they
+    /// panic instead when adding a key already in the batch. Batches
+    /// detecting conflicts should be filled with the `try_` variants.
+    pub fn with_conflict_detection() -> WriteBatch {
+        WriteBatch {
+            keys: Some(RefCell::new(HashSet::new())),
+            ..WriteBatch::new()
+        }
     }
 
     /// Clear the writebatch
     pub fn clear(&self) {
         unsafe { leveldb_writebatch_clear(self.write_batch.ptr) };
+        self.size.set(BATCH_HEADER_SIZE);
+        if let Some(keys) = &self.keys {
+            keys.borrow_mut().clear();
+        }
+    }
+
+    // Remember `key`, failing if conflict detection is on and it was added before
+    fn track_key(&self, key: &[u8]) -> Result<(), Error> {
+        match &self.keys {
+            Some(keys) if !keys.borrow_mut().insert(key.to_vec()) => Err(Error::duplicate_key(key)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The size of the batch as written to the log
+    ///
+    /// Tracks leveldb's representation as operations are added: a fixed header,
+    /// plus per operation a tag byte and the length-prefixed key (and value).
+    pub fn approximate_size(&self) -> usize {
+        self.size.get()
+    }
+
+    fn add_size(&self, data: &[u8]) {
+        self.size
+            .set(self.size.get() + varint_len(data.len()) + data.len());
     }
 
     /// Batch a put operation
+    ///
+    /// Panics like `put_u8` on a duplicate key.
     pub fn put(&self, key: &dyn IntoLevelDBKey, value: &[u8]) {
         let _ = key.as_u8_slice_for_write(&|k| {
             self.put_u8(k, value);
@@ -78,7 +237,34 @@ impl WriteBatch {
         });
     }
 
+    /// Batch a put operation
+    ///
+    /// Panics if the batch detects conflicts and already holds `key`, see
+    /// `with_conflict_detection`.
     pub fn put_u8(&self, key: &[u8], value: &[u8]) {
+        let tracked = self.track_key(key);
+        assert!(tracked.is_ok(), "{}", DUPLICATE_UNCHECKED);
+        self.push_put(key, value);
+    }
+
+    /// Batch a put operation, failing if the batch detects conflicts and
+    /// already holds `key`, see `with_conflict_detection`
+    pub fn try_put(&self, key: &dyn IntoLevelDBKey, value: &[u8]) -> Result<(), Error> {
+        key.as_u8_slice_for_write(&|k| self.try_put_u8(k, value))
+    }
+
+    /// Batch a put operation, failing if the batch detects conflicts and
+    /// already holds `key`, see `with_conflict_detection`
+    pub fn try_put_u8(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.track_key(key)?;
+        self.push_put(key, value);
+        Ok(())
+    }
+
+    fn push_put(&self, key: &[u8], value: &[u8]) {
+        self.size.set(self.size.get() + 1);
+        self.add_size(key);
+        self.add_size(value);
         unsafe {
             leveldb_writebatch_put(
                 self.write_batch.ptr,
@@ -90,7 +276,16 @@ impl WriteBatch {
         }
     }
 
+    /// Batch a put of a UTF-8 string value
+    ///
+    /// The key can be anything viewable as bytes, like a `&str` or `String`.
+    pub fn put_str<K: AsRef<[u8]> + ?Sized>(&self, key: &K, value: &str) {
+        self.put_u8(key.as_ref(), value.as_bytes());
+    }
+
     /// Batch a delete operation
+    ///
+    /// Panics like `delete_u8` on a duplicate key.
     pub fn delete(&self, key: &dyn IntoLevelDBKey) {
         let _ = key.as_u8_slice_for_write(&|k| {
             self.delete_u8(k);
@@ -99,7 +294,33 @@ impl WriteBatch {
         });
     }
 
+    /// Batch a delete operation
+    ///
+    /// Panics if the batch detects conflicts and already holds `key`, see
+    /// `with_conflict_detection`.
     pub fn delete_u8(&self, key: &[u8]) {
+        let tracked = self.track_key(key);
+        assert!(tracked.is_ok(), "{}", DUPLICATE_UNCHECKED);
+        self.push_delete(key);
+    }
+
+    /// Batch a delete operation, failing if the batch detects conflicts and
+    /// already holds `key`, see `with_conflict_detection`
+    pub fn try_delete(&self, key: &dyn IntoLevelDBKey) -> Result<(), Error> {
+        key.as_u8_slice_for_write(&|k| self.try_delete_u8(k))
+    }
+
+    /// Batch a delete operation, failing if the batch detects conflicts and
+    /// already holds `key`, see `with_conflict_detection`
+    pub fn try_delete_u8(&self, key: &[u8]) -> Result<(), Error> {
+        self.track_key(key)?;
+        self.push_delete(key);
+        Ok(())
+    }
+
+    fn push_delete(&self, key: &[u8]) {
+        self.size.set(self.size.get() + 1);
+        self.add_size(key);
         unsafe {
             leveldb_writebatch_delete(
                 self.write_batch.ptr,
@@ -109,6 +330,37 @@ impl WriteBatch {
         }
     }
 
+    /// Batch a delete for every key in the range `[start, end)`
+    ///
+    /// leveldb has no range deletes, so the keys are read from `database` when
+    /// this is called and deleted one by one. Keys written to the range after
+    /// the call, but before the batch is written, are not deleted.
+    ///
+    /// If the batch detects conflicts and already holds one of the keys, a
+    /// duplicate key error is returned, the deletes of the keys before it
+    /// stay in the batch.
+    pub fn delete_range(
+        &self,
+        database: &Database,
+        options: &ReadOptions,
+        start: &[u8],
+        end: &[u8],
+    ) -> Result<(), Error> {
+        let mut keys = database.keys_iter(options).from(start);
+        while let Some(key) = keys.try_next()? {
+            if key.as_slice() >= end {
+                break;
+            }
+            self.try_delete_u8(&key)?;
+        }
+        Ok(())
+    }
+
+    /// Batch a delete of a key viewable as bytes, like a `&str` or `String`
+    pub fn delete_str<K: AsRef<[u8]> + ?Sized>(&self, key: &K) {
+        self.delete_u8(key.as_ref());
+    }
+
     /// Iterate over the writeBatch, returning the resulting iterator
     pub fn iterate<T: WriteBatchIterator>(&mut self, iterator: Box<T>) -> Box<T> {
         unsafe {
@@ -124,6 +376,15 @@ impl WriteBatch {
     }
 }
 
+fn varint_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
 /// A trait for iterators to iterate over written batches and check their validity.
 pub trait WriteBatchIterator {
     /// Callback for put items
@@ -142,8 +403,8 @@ extern "C" fn put_callback<T: WriteBatchIterator>(
 ) {
     unsafe {
         let iter: &mut T = &mut *(state as *mut T);
-        let key_slice = slice::from_raw_parts::<u8>(key as *const u8, key_len);
-        let val_slice = slice::from_raw_parts::<u8>(val as *const u8, val_len);
+        let key_slice = raw_slice(key, key_len);
+        let val_slice = raw_slice(val, val_len);
 
         iter.put_u8(key_slice, val_slice);
     }
@@ -156,7 +417,7 @@ extern "C" fn deleted_callback<T: WriteBatchIterator>(
 ) {
     unsafe {
         let iter: &mut T = &mut *(state as *mut T);
-        let key_slice = slice::from_raw_parts::<u8>(key as *const u8, key_len);
+        let key_slice = raw_slice(key, key_len);
 
         iter.deleted_u8(key_slice);
     }
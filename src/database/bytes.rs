@@ -1,13 +1,31 @@
-use std::slice;
+use libc::{c_char, size_t};
+use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard};
+use std::{fmt, slice, str};
+
+/// Borrow `len` bytes at `ptr` handed out by leveldb.
+///
+/// `slice::from_raw_parts` requires a non-null pointer even for an empty slice,
+/// which leveldb doesn't guarantee for empty keys and values.
+///
+/// # Safety
+/// Unless `len` is 0, `ptr` must point to `len` bytes valid for the lifetime `'b`.
+pub(crate) unsafe fn raw_slice<'b>(ptr: *const c_char, len: size_t) -> &'b [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr as *const u8, len)
+    }
+}
 
 /// Bytes allocated by leveldb
 ///
 /// It's basically the same thing as `Box<[u8]>` except that it uses
 /// leveldb_free() as a destructor.
 pub struct Bytes {
-    // We use static reference instead of pointer to inform the compiler that
-    // it can't be null. (Because `NonZero` is unstable now.)
-    bytes: &'static mut u8,
+    // leveldb may return a zero-sized allocation for an empty value, which
+    // must not be referenced as a `u8`
+    bytes: NonNull<u8>,
     size: usize,
     // Tells the compiler that we own u8
     _marker: ::std::marker::PhantomData<u8>,
@@ -20,15 +38,11 @@ impl Bytes {
     ///
     /// # Safety
     pub unsafe fn from_raw(ptr: *mut u8, size: usize) -> Option<Self> {
-        if ptr.is_null() {
-            None
-        } else {
-            Some(Bytes {
-                bytes: &mut *ptr,
-                size,
-                _marker: Default::default(),
-            })
-        }
+        NonNull::new(ptr).map(|bytes| Bytes {
+            bytes,
+            size,
+            _marker: Default::default(),
+        })
     }
 
     /// Creates instance of `Bytes` from leveldb-allocated data without null checking.
@@ -36,7 +50,7 @@ impl Bytes {
     /// # Safety
     pub unsafe fn from_raw_unchecked(ptr: *mut u8, size: usize) -> Self {
         Bytes {
-            bytes: &mut *ptr,
+            bytes: NonNull::new_unchecked(ptr),
             size,
             _marker: Default::default(),
         }
@@ -48,7 +62,7 @@ impl Drop for Bytes {
         unsafe {
             use libc::c_void;
 
-            leveldb_sys::leveldb_free(self.bytes as *mut u8 as *mut c_void);
+            leveldb_sys::leveldb_free(self.bytes.as_ptr() as *mut c_void);
         }
     }
 }
@@ -57,13 +71,13 @@ impl std::ops::Deref for Bytes {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        unsafe { slice::from_raw_parts(self.bytes, self.size) }
+        unsafe { slice::from_raw_parts(self.bytes.as_ptr(), self.size) }
     }
 }
 
 impl std::ops::DerefMut for Bytes {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { slice::from_raw_parts_mut(self.bytes as *mut u8, self.size) }
+        unsafe { slice::from_raw_parts_mut(self.bytes.as_ptr(), self.size) }
     }
 }
 
@@ -101,4 +115,168 @@ impl From<Bytes> for Box<[u8]> {
     fn from(bytes: Bytes) -> Self {
         bytes.as_ref().to_owned().into_boxed_slice()
     }
-}
\ No newline at end of file
+}
+
+/// Values allocated by leveldb, read in one go
+///
+/// The values stay in the buffers leveldb allocated for them and are freed
+/// together when the bundle is dropped. Slices returned by `get` borrow from
+/// the bundle and can't outlive it, the bundle itself is independent of the
+/// database it was read from.
+pub struct ValueBundle {
+    values: Vec<Option<Bytes>>,
+}
+
+impl ValueBundle {
+    pub(crate) fn new(values: Vec<Option<Bytes>>) -> ValueBundle {
+        ValueBundle { values }
+    }
+
+    /// The value read for the `i`-th key
+    ///
+    /// Returns `None` if the key was missing or `i` is out of bounds.
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        self.values.get(i)?.as_deref()
+    }
+
+    /// The number of keys read
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether no keys were read
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// A pool of reusable buffers for reading values, see `Database::get_pooled`
+///
+/// Buffers are handed out as `PooledBuf` and returned to the pool when dropped.
+/// At most `max_buffers` idle buffers are kept, further ones are freed.
+/// The pool can be shared between threads.
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Create an empty pool keeping at most `max_buffers` idle buffers.
+    pub fn new(max_buffers: usize) -> BufferPool {
+        BufferPool {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Take a buffer holding a copy of `data` from the pool.
+    pub fn fill(&self, data: &[u8]) -> PooledBuf<'_> {
+        let mut buf = self.lock().pop().unwrap_or_default();
+        buf.clear();
+        buf.extend_from_slice(data);
+        PooledBuf { buf, pool: self }
+    }
+
+    /// The number of idle buffers in the pool.
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Vec<u8>>> {
+        // a poisoned pool still holds valid buffers
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A buffer taken from a `BufferPool`, returned to it on drop
+pub struct PooledBuf<'p> {
+    buf: Vec<u8>,
+    pool: &'p BufferPool,
+}
+
+impl Drop for PooledBuf<'_> {
+    fn drop(&mut self) {
+        let mut buffers = self.pool.lock();
+        if buffers.len() < self.pool.max_buffers {
+            buffers.push(std::mem::take(&mut self.buf));
+        }
+    }
+}
+
+impl std::ops::Deref for PooledBuf<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.buf
+    }
+}
+
+impl AsRef<[u8]> for PooledBuf<'_> {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// Computes the smallest key that is greater than all keys starting with `prefix`.
+///
+/// Trailing `0xFF` bytes are dropped and the last remaining byte is incremented,
+/// giving an exclusive upper bound for a prefix scan. Returns `None` if the prefix
+/// consists only of `0xFF` bytes (or is empty), as no such bound exists.
+pub fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|&b| b != 0xFF)?;
+    let mut successor = prefix[..=last].to_vec();
+    successor[last] += 1;
+
+    Some(successor)
+}
+
+/// Computes the immediate successor of `key` in bytewise order.
+///
+/// The successor is `key` followed by a `0x00` byte: no key sorts strictly
+/// between the two, so it turns an inclusive bound into an exclusive one.
+pub fn next_key(key: &[u8]) -> Vec<u8> {
+    let mut next = Vec::with_capacity(key.len() + 1);
+    next.extend_from_slice(key);
+    next.push(0);
+
+    next
+}
+
+/// Computes the largest key that sorts strictly before `key` in bytewise order
+/// and is at most as long as `key`.
+///
+/// A trailing `0x00` byte is dropped, giving the immediate predecessor, the
+/// inverse of `next_key`. Otherwise the last byte is decremented; note that
+/// longer keys, like the result followed by `0xFF` bytes, still sort between
+/// the result and `key`. Returns `None` for the empty key, which has no
+/// predecessor.
+pub fn prev_key(key: &[u8]) -> Option<Vec<u8>> {
+    let (&last, init) = key.split_last()?;
+    let mut prev = init.to_vec();
+    if last != 0 {
+        prev.push(last - 1);
+    }
+
+    Some(prev)
+}
+
+/// Displays a key readably, e.g. in error messages
+///
+/// Keys that are valid UTF-8 without control characters are shown as text,
+/// any other key as `hex:` followed by its bytes in hex.
+pub struct DisplayKey<'a>(pub &'a [u8]);
+
+impl fmt::Display for DisplayKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match str::from_utf8(self.0) {
+            Ok(text) if !text.chars().any(char::is_control) => f.write_str(text),
+            _ => {
+                f.write_str("hex:")?;
+                for byte in self.0 {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
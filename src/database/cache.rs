@@ -1,12 +1,16 @@
 //! Structs and traits to work with the leveldb cache.
 use leveldb_sys::{leveldb_cache_create_lru, leveldb_cache_destroy, leveldb_cache_t};
 use libc::size_t;
+use std::sync::Arc;
 
 #[allow(missing_docs)]
 struct RawCache {
     ptr: *mut leveldb_cache_t,
 }
 
+unsafe impl Send for RawCache {}
+unsafe impl Sync for RawCache {}
+
 impl Drop for RawCache {
     fn drop(&mut self) {
         unsafe {
@@ -16,19 +20,49 @@ impl Drop for RawCache {
 }
 
 /// Represents a leveldb cache
+///
+/// Clones refer to the same underlying cache, which is destroyed once the
+/// last clone is dropped.
+///
+/// leveldb's LRU cache is already sharded internally: blocks are spread by the
+/// hash of their key over 16 shards, each guarded by its own mutex and holding
+/// an equal part of the capacity. Concurrent reads of different blocks thus
+/// rarely contend on the same lock. The C API offers no way to change the
+/// number of shards.
+#[derive(Clone)]
 pub struct Cache {
-    raw: RawCache,
+    raw: Arc<RawCache>,
+}
+
+// the size of the cache leveldb creates when none is given
+const DEFAULT_CAPACITY: size_t = 8 * 1024 * 1024;
+
+impl Default for Cache {
+    /// A cache of 8 MiB, the size leveldb uses when no cache is given
+    fn default() -> Self {
+        Cache::new(DEFAULT_CAPACITY)
+    }
 }
 
 impl Cache {
-    /// Create a leveldb LRU cache of a given size
+    /// Create a leveldb LRU cache holding up to `size` bytes
     pub fn new(size: size_t) -> Cache {
         let cache = unsafe { leveldb_cache_create_lru(size) };
         Cache {
-            raw: RawCache { ptr: cache },
+            raw: Arc::new(RawCache { ptr: cache }),
         }
     }
 
+    /// Create a cache holding up to `kb` KiB
+    pub fn kb(kb: size_t) -> Cache {
+        Cache::new(kb * 1024)
+    }
+
+    /// Create a cache holding up to `mb` MiB
+    pub fn mb(mb: size_t) -> Cache {
+        Cache::new(mb * 1024 * 1024)
+    }
+
     #[allow(missing_docs)]
     pub fn raw_ptr(&self) -> *mut leveldb_cache_t {
         self.raw.ptr
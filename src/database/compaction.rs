@@ -1,6 +1,38 @@
+//! Compaction of the database
+//!
+//! leveldb's C API offers no hook to run a filter while leveldb compacts
+//! on its own. `filter_compact` instead rewrites a key range explicitly
+//! before compacting it.
+use super::batch::{Batch, WriteBatch};
+use super::error::Error;
+use super::iterator::{Iterable, LevelDBIterator};
+use super::options::{ReadOptions, WriteOptions};
 use super::Database;
 use leveldb_sys::leveldb_compact_range;
 use libc::{c_char, size_t};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+// leveldb's config::kL0_CompactionTrigger
+const L0_COMPACTION_TRIGGER: u64 = 4;
+
+// the size leveldb allows for level 1 before compacting it, each further
+// level may hold ten times as much
+const LEVEL1_MAX_MB: f64 = 10.0;
+
+const BYTES_PER_MB: f64 = 1_048_576.0;
+
+/// The decision of a compaction filter for a single entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Keep the entry unchanged
+    Keep,
+    /// Delete the entry
+    Remove,
+    /// Replace the value of the entry
+    Change(Vec<u8>),
+}
 
 pub trait Compaction<'a> {
     fn compact(&self, start: &'a [u8], limit: &'a [u8]);
@@ -18,4 +50,211 @@ impl<'a> Compaction<'a> for Database {
             );
         }
     }
-}
\ No newline at end of file
+}
+
+impl Database {
+    /// Apply `filter` to all entries between `start` and `limit` (inclusive),
+    /// then compact the range.
+    ///
+    /// Removals and changes are written in a single batch, entries written
+    /// concurrently to the range may not be seen by the filter.
+    pub fn filter_compact<F>(&self, start: &[u8], limit: &[u8], mut filter: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &[u8]) -> FilterDecision,
+    {
+        let batch = WriteBatch::new();
+
+        for (key, value) in self.iter(&ReadOptions::new()).from(start).to(limit) {
+            match filter(&key, &value) {
+                FilterDecision::Keep => {}
+                FilterDecision::Remove => batch.delete_u8(&key),
+                FilterDecision::Change(value) => batch.put_u8(&key, &value),
+            }
+        }
+        self.write(&WriteOptions::new(), &batch)?;
+        self.compact(start, limit);
+
+        Ok(())
+    }
+
+    /// Whether leveldb has compaction work queued
+    ///
+    /// This is derived from the `leveldb.stats` property, see
+    /// `stats_need_compaction`. It is advisory only, leveldb may start a
+    /// compaction right after this returned false, and compactions triggered
+    /// by reads aren't visible at all.
+    pub fn compaction_pending(&self) -> bool {
+        match self.property("leveldb.stats") {
+            Some(stats) => stats_need_compaction(&stats),
+            None => false,
+        }
+    }
+}
+
+/// Whether the `leveldb.stats` table `stats` shows compaction work
+///
+/// Uses the same thresholds leveldb uses to schedule compactions: too many
+/// files at level 0, or a level grown beyond its size limit.
+pub fn stats_need_compaction(stats: &str) -> bool {
+    // each row of the table starts with the level followed by its number of
+    // files and size in MB
+    stats
+        .lines()
+        .skip_while(|line| !line.starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let level: i32 = columns.next()?.parse().ok()?;
+            let files: u64 = columns.next()?.parse().ok()?;
+            let size_mb: f64 = columns.next()?.parse().ok()?;
+            Some((level, files, size_mb))
+        })
+        .any(|(level, files, size_mb)| {
+            if level == 0 {
+                files >= L0_COMPACTION_TRIGGER
+            } else {
+                size_mb > LEVEL1_MAX_MB * 10f64.powi(level - 1)
+            }
+        })
+}
+
+/// Tracks the compaction work of a database between samples, e.g. to compute
+/// the bytes compacted per minute
+///
+/// Each sample reads the cumulative counters, the deltas report the work done
+/// between the last two samples. Bytes written are summed over the levels of
+/// the `leveldb.stats` table, which reports them in whole MB, so small deltas
+/// read as 0. Compactions are counted from the info log (see
+/// `Database::info_log`), including the flushes of the memtable to level 0.
+/// Each sample only reads the lines appended since the last one. If the log
+/// shrank in between, e.g. because it was rotated to `LOG.old`, the new log
+/// is counted from its start.
+pub struct CompactionTracker<'a> {
+    database: &'a Database,
+    // how far the info log was read, and the compactions counted so far
+    log_offset: u64,
+    compactions: u64,
+    previous: CompactionSample,
+    latest: CompactionSample,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CompactionSample {
+    taken: Instant,
+    bytes_written: u64,
+    compactions: u64,
+}
+
+impl<'a> CompactionTracker<'a> {
+    /// Start tracking `database`, taking the first sample
+    pub fn new(database: &'a Database) -> Result<CompactionTracker<'a>, Error> {
+        let mut tracker = CompactionTracker {
+            database,
+            log_offset: 0,
+            compactions: 0,
+            previous: CompactionSample::empty(),
+            latest: CompactionSample::empty(),
+        };
+        let sample = tracker.take_sample()?;
+        tracker.previous = sample;
+        tracker.latest = sample;
+
+        Ok(tracker)
+    }
+
+    /// Take a new sample, the deltas then cover the time since the last one
+    pub fn sample(&mut self) -> Result<(), Error> {
+        let sample = self.take_sample()?;
+        self.previous = self.latest;
+        self.latest = sample;
+
+        Ok(())
+    }
+
+    /// The bytes written by compactions between the last two samples
+    pub fn bytes_written_delta(&self) -> u64 {
+        self.latest
+            .bytes_written
+            .saturating_sub(self.previous.bytes_written)
+    }
+
+    /// The number of compactions between the last two samples
+    pub fn compactions_delta(&self) -> u64 {
+        self.latest
+            .compactions
+            .saturating_sub(self.previous.compactions)
+    }
+
+    /// The time between the last two samples
+    pub fn elapsed(&self) -> Duration {
+        self.latest
+            .taken
+            .saturating_duration_since(self.previous.taken)
+    }
+
+    fn take_sample(&mut self) -> Result<CompactionSample, Error> {
+        let stats = self.database.property("leveldb.stats").unwrap_or_default();
+        self.count_compactions()?;
+
+        Ok(CompactionSample {
+            taken: Instant::now(),
+            bytes_written: stats_bytes_written(&stats),
+            compactions: self.compactions,
+        })
+    }
+
+    // Count the compactions in the lines appended to the info log since the
+    // last read
+    fn count_compactions(&mut self) -> Result<(), Error> {
+        let mut log = File::open(self.database.info_log_file())?;
+        if log.metadata()?.len() < self.log_offset {
+            self.log_offset = 0;
+        }
+        log.seek(SeekFrom::Start(self.log_offset))?;
+        let mut appended = Vec::new();
+        log.read_to_end(&mut appended)?;
+
+        // a line leveldb is still writing is left for the next read
+        let complete = appended
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |end| end + 1);
+        self.compactions += String::from_utf8_lossy(&appended[..complete])
+            .lines()
+            .filter(|line| is_compaction_line(line))
+            .count() as u64;
+        self.log_offset += complete as u64;
+
+        Ok(())
+    }
+}
+
+impl CompactionSample {
+    fn empty() -> CompactionSample {
+        CompactionSample {
+            taken: Instant::now(),
+            bytes_written: 0,
+            compactions: 0,
+        }
+    }
+}
+
+// Sum the Write(MB) column, the last one of the `leveldb.stats` table
+fn stats_bytes_written(stats: &str) -> u64 {
+    let written_mb: f64 = stats
+        .lines()
+        .skip_while(|line| !line.starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(5)?.parse::<f64>().ok())
+        .sum();
+
+    (written_mb * BYTES_PER_MB) as u64
+}
+
+// The info log lines leveldb writes when a compaction finished: memtable
+// flushes, moves of a file to the next level, and merging compactions
+fn is_compaction_line(line: &str) -> bool {
+    (line.contains("Level-0 table #") && !line.ends_with("started"))
+        || line.contains(" Moved #")
+        || line.contains(" Compacted ")
+}
@@ -4,16 +4,23 @@
 //! Comparators allow to override this comparison.
 //! The ordering of keys introduced by the comparator influences iteration order.
 //! Databases written with one Comparator cannot be opened with another.
+use super::bytes::raw_slice;
+use super::util::Endian;
 use leveldb_sys::*;
 use libc::{c_char, c_void, size_t};
 use std::cmp::Ordering;
-use std::slice;
+use std::ffi::CString;
 
 /// A comparator has two important functions:
 ///
 /// * the name function returns a fixed name to detect errors when
 ///   opening databases with a different name
 /// * The comparison implementation
+///
+/// leveldb's C API doesn't expose the key shortening hooks of its comparators
+/// (`FindShortestSeparator` and `FindShortSuccessor`): comparators created
+/// through it always keep separators and successors unchanged. Index blocks
+/// therefore store full keys, which is always correct but can make them larger.
 pub trait Comparator {
     /// Return the name of the Comparator
     fn name(&self) -> *const c_char;
@@ -32,8 +39,145 @@ pub trait Comparator {
 #[derive(Copy, Clone)]
 pub struct DefaultComparator;
 
+/// A comparator ordering keys by a fixed-length integer prefix
+///
+/// The first `prefix_len` bytes of a key are decoded as an unsigned integer of
+/// the given byte order and compared numerically, the rest of the key is
+/// compared bytewise. Keys shorter than the prefix sort before all other keys.
+///
+/// The name includes the prefix length and byte order, so a database can only
+/// be reopened with the same configuration.
+pub struct NumericPrefixComparator {
+    prefix_len: usize,
+    endian: Endian,
+    name: CString,
+}
+
+impl NumericPrefixComparator {
+    /// Create a comparator for prefixes of `prefix_len` bytes, at most 16.
+    pub fn new(prefix_len: usize, endian: Endian) -> NumericPrefixComparator {
+        assert!(
+            prefix_len <= 16,
+            "numeric prefix of {} bytes exceeds 16 bytes",
+            prefix_len
+        );
+        let endian_name = match endian {
+            Endian::Big => "be",
+            Endian::Little => "le",
+        };
+        let name = format!(
+            "leveldb.NumericPrefixComparator.{}.{}",
+            prefix_len, endian_name
+        );
+
+        NumericPrefixComparator {
+            prefix_len,
+            endian,
+            name: CString::new(name).unwrap(),
+        }
+    }
+
+    fn decode(&self, prefix: &[u8]) -> u128 {
+        let fold = |value: u128, byte: &u8| value << 8 | *byte as u128;
+        match self.endian {
+            Endian::Big => prefix.iter().fold(0, fold),
+            Endian::Little => prefix.iter().rev().fold(0, fold),
+        }
+    }
+}
+
+impl Comparator for NumericPrefixComparator {
+    fn name(&self) -> *const c_char {
+        self.name.as_ptr()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let n = self.prefix_len;
+        match (a.len() >= n, b.len() >= n) {
+            (true, true) => self
+                .decode(&a[..n])
+                .cmp(&self.decode(&b[..n]))
+                .then_with(|| a[n..].cmp(&b[n..])),
+            (false, false) => a.cmp(b),
+            (false, true) => Ordering::Less,
+            (true, false) => Ordering::Greater,
+        }
+    }
+}
+
+/// The order of a segment of a `TupleComparator`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Smaller bytes first
+    Ascending,
+    /// Larger bytes first
+    Descending,
+}
+
+/// A comparator ordering keys made of fixed-length segments
+///
+/// Each segment is compared bytewise in its own order, the first segment that
+/// differs decides. Bytes after the last segment are compared ascending. The
+/// segments must have a fixed length for keys to be split correctly, a key
+/// shorter than the segments sorts by the bytes it has.
+///
+/// The name encodes the segments, so a database can only be reopened with the
+/// same composition.
+pub struct TupleComparator {
+    segments: Vec<(usize, SortOrder)>,
+    name: CString,
+}
+
+impl TupleComparator {
+    /// Create a comparator from `(length, order)` segments
+    pub fn new(segments: &[(usize, SortOrder)]) -> TupleComparator {
+        let parts: Vec<String> = segments
+            .iter()
+            .map(|&(len, order)| match order {
+                SortOrder::Ascending => format!("{}a", len),
+                SortOrder::Descending => format!("{}d", len),
+            })
+            .collect();
+        let name = format!("leveldb.TupleComparator.{}", parts.join("."));
+
+        TupleComparator {
+            segments: segments.to_vec(),
+            name: CString::new(name).unwrap(),
+        }
+    }
+}
+
+impl Comparator for TupleComparator {
+    fn name(&self) -> *const c_char {
+        self.name.as_ptr()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let mut start = 0;
+        for &(len, order) in &self.segments {
+            let ordering = segment(a, start, len).cmp(segment(b, start, len));
+            let ordering = match order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            start += len;
+        }
+        a[start.min(a.len())..].cmp(&b[start.min(b.len())..])
+    }
+}
+
+// the bytes of `key` in `[start, start + len)`, cut off at the end of the key
+fn segment(key: &[u8], start: usize, len: usize) -> &[u8] {
+    let start = start.min(key.len());
+    let end = (start + len).min(key.len());
+    &key[start..end]
+}
+
 /// # Safety
-unsafe trait InternalComparator: Comparator
+pub(crate) unsafe trait InternalComparator: Comparator
 where
     Self: Sized,
 {
@@ -50,8 +194,8 @@ where
         b_len: size_t,
     ) -> i32 {
         unsafe {
-            let a_slice = slice::from_raw_parts::<u8>(a as *const u8, a_len);
-            let b_slice = slice::from_raw_parts::<u8>(b as *const u8, b_len);
+            let a_slice = raw_slice(a, a_len);
+            let b_slice = raw_slice(b, b_len);
             let x = &*(state as *mut Self);
 
             match x.compare(a_slice, b_slice) {
@@ -1,13 +1,24 @@
-use super::bytes::Bytes;
-use super::comparator::{create_comparator, Comparator};
+use super::batch::{Batch, WriteBatch};
+use super::bytes::{BufferPool, Bytes, PooledBuf, ValueBundle};
+use super::comparator::{Comparator, InternalComparator};
 use super::error::Error;
-use super::key::IntoLevelDBKey;
+use super::iterator::{raw_key, raw_value, Iterable, LevelDBIterator};
+use super::key::{IntoLevelDBKey, KeyTag, TypedKey};
 use super::options::*;
+use super::snapshots::{Snapshot, SnapshotRegistry};
 use leveldb_sys::*;
-use libc::{c_char, size_t};
-use std::ffi::CString;
-use std::path::Path;
+use libc::{c_char, c_int, c_void, size_t};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering as AtomicOrdering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[allow(missing_docs)]
 #[derive(Debug)]
@@ -28,6 +39,44 @@ impl Drop for RawDB {
 #[derive(Debug)]
 pub(crate) struct RawComparator {
     ptr: *mut leveldb_comparator_t,
+    // the comparator owned by `ptr` and its callbacks, to compare keys from Rust
+    state: *mut c_void,
+    name: extern "C" fn(*mut c_void) -> *const c_char,
+    compare: extern "C" fn(*mut c_void, *const c_char, size_t, *const c_char, size_t) -> i32,
+}
+
+impl RawComparator {
+    fn new<C: Comparator>(comparator: C) -> RawComparator {
+        let state = Box::into_raw(Box::new(comparator)) as *mut c_void;
+        let name = <C as InternalComparator>::name;
+        let compare = <C as InternalComparator>::compare;
+        let ptr = unsafe {
+            leveldb_comparator_create(state, <C as InternalComparator>::destructor, compare, name)
+        };
+        RawComparator {
+            ptr,
+            state,
+            name,
+            compare,
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        unsafe { CStr::from_ptr((self.name)(self.state)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub(crate) fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let order = (self.compare)(
+            self.state,
+            a.as_ptr() as *const c_char,
+            a.len(),
+            b.as_ptr() as *const c_char,
+            b.len(),
+        );
+        order.cmp(&0)
+    }
 }
 
 #[allow(missing_docs)]
@@ -39,25 +88,216 @@ impl Drop for RawComparator {
     }
 }
 
+// Read options handed to leveldb, never changed after they were created.
+// leveldb only reads them during a lookup, so concurrent reads can share them.
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub(crate) struct RawReadOptions {
+    ptr: *mut leveldb_readoptions_t,
+}
+
+#[allow(missing_docs)]
+impl Drop for RawReadOptions {
+    fn drop(&mut self) {
+        unsafe {
+            leveldb_readoptions_destroy(self.ptr);
+        }
+    }
+}
+
+/// A key-value pair read from the database
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+// how often `open_with` checks whether the lock was released
+const OPEN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// leveldb's config::kNumLevels
+const NUM_LEVELS: usize = 7;
+
+/// Aggregated statistics of an open database
+///
+/// Collected from leveldb's string properties, fields are left empty if a
+/// property is not available.
+#[derive(Debug, Clone, Default)]
+pub struct DbStats {
+    /// Number of table files at each level, starting with level 0
+    pub num_files_per_level: Vec<u64>,
+    /// Approximate number of bytes used to hold in-memory data
+    pub approximate_memory_usage: Option<u64>,
+    /// The raw `leveldb.stats` output
+    pub stats: String,
+    /// The raw `leveldb.sstables` output
+    pub sstables: String,
+}
+
+// Paths of the databases currently open in this process
+fn open_paths() -> &'static Mutex<HashSet<PathBuf>> {
+    static INIT: Once = Once::new();
+    static PATHS: AtomicPtr<Mutex<HashSet<PathBuf>>> = AtomicPtr::new(ptr::null_mut());
+
+    INIT.call_once(|| {
+        let paths = Box::new(Mutex::new(HashSet::new()));
+        PATHS.store(Box::into_raw(paths), AtomicOrdering::Release);
+    });
+    unsafe { &*PATHS.load(AtomicOrdering::Acquire) }
+}
+
+/// The registration of an open database path, removed again on drop
+#[derive(Debug)]
+struct OpenPath {
+    path: PathBuf,
+}
+
+impl OpenPath {
+    fn register(name: &Path) -> Result<OpenPath, Error> {
+        let path = canonical_path(name);
+        let mut paths = open_paths().lock().unwrap_or_else(|e| e.into_inner());
+
+        if paths.insert(path.clone()) {
+            Ok(OpenPath { path })
+        } else {
+            Err(Error::already_open(&path))
+        }
+    }
+}
+
+impl Drop for OpenPath {
+    fn drop(&mut self) {
+        let mut paths = open_paths().lock().unwrap_or_else(|e| e.into_inner());
+        paths.remove(&self.path);
+    }
+}
+
+// resolve the path, which may not exist yet, to compare it across opens
+fn canonical_path(name: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(name) {
+        return path;
+    }
+    let absolute = match std::env::current_dir() {
+        Ok(dir) => dir.join(name),
+        Err(_) => name.to_path_buf(),
+    };
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(file_name)) => match fs::canonicalize(parent) {
+            Ok(parent) => parent.join(file_name),
+            Err(_) => absolute,
+        },
+        _ => absolute,
+    }
+}
+
 #[derive(Debug)]
 pub struct Database {
     pub(crate) database: RawDB,
+    // declared after the handle, so the path is only released once
+    // leveldb has released its lock
+    open_path: OpenPath,
     // this holds a reference passed into leveldb
-    // it is never read from Rust, but must be kept around
-    #[allow(dead_code)]
+    // it is read from Rust when reopening and when merging databases
     pub(crate) comparator: Option<RawComparator>,
+    pub(crate) path: PathBuf,
+    // fields are dropped in declaration order, so a cache shared through
+    // the options outlives the database handle using it
+    pub(crate) options: Options,
+    pub(crate) snapshots: SnapshotRegistry,
+    // serializes the commits of transactions, and the writes of a `TtlDatabase`
+    // with its removal of expired entries
+    pub(crate) commit_lock: Mutex<()>,
+    // the default read options, created once for `get_default`
+    default_read_options: RawReadOptions,
+    // whether `Options::auto_repair` repaired the database while opening it
+    repaired: bool,
 }
 
 unsafe impl Sync for Database {}
 unsafe impl Send for Database {}
 
 impl Database {
-    fn new(database: *mut leveldb_t, comparator: Option<*mut leveldb_comparator_t>) -> Database {
-        let raw_comp = comparator.map(|p| RawComparator { ptr: p });
+    fn new(
+        database: *mut leveldb_t,
+        open_path: OpenPath,
+        path: &Path,
+        options: Options,
+        comparator: Option<RawComparator>,
+        repaired: bool,
+    ) -> Database {
+        let default_read_options = RawReadOptions {
+            ptr: unsafe {
+                c_readoptions(&ReadOptions {
+                    verify_checksums: options.default_verify_checksums,
+                    ..ReadOptions::new()
+                })
+            },
+        };
 
         Database {
             database: RawDB { ptr: database },
-            comparator: raw_comp,
+            open_path,
+            comparator,
+            path: path.to_path_buf(),
+            options,
+            snapshots: SnapshotRegistry::default(),
+            commit_lock: Mutex::new(()),
+            default_read_options,
+            repaired,
+        }
+    }
+
+    // Open the database, also returning whether it had to be repaired first
+    unsafe fn open_raw(
+        name: &Path,
+        options: &Options,
+        comparator: Option<&RawComparator>,
+    ) -> Result<(*mut leveldb_t, bool), Error> {
+        if options.create_parents {
+            if let Some(parent) = name.parent() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let c_string = CString::new(name.to_str().unwrap()).unwrap();
+        let c_name = c_string.as_bytes_with_nul().as_ptr() as *const c_char;
+        let c_options = c_options(options, comparator.map(|c| c.ptr));
+
+        let result = match Database::open_c(c_name, c_options) {
+            Err(e) if options.auto_repair && e.is_corruption() => {
+                // repair with the same comparator, then retry once
+                let mut error = ptr::null_mut();
+                leveldb_repair_db(c_options, c_name, &mut error);
+                if error.is_null() {
+                    Database::open_c(c_name, c_options)
+                        .map(|db| (db, true))
+                        .map_err(|_| e)
+                } else {
+                    drop(Error::new_from_char(error));
+                    Err(e)
+                }
+            }
+            result => result.map(|db| (db, false)),
+        };
+        leveldb_options_destroy(c_options);
+
+        if let (Ok((db, _)), Some(log_path)) = (&result, &options.info_log_path) {
+            if let Err(e) = move_info_log(&name.join("LOG"), log_path) {
+                leveldb_close(*db);
+                return Err(e.into());
+            }
+        }
+
+        result
+    }
+
+    unsafe fn open_c(
+        c_name: *const c_char,
+        c_options: *mut leveldb_options_t,
+    ) -> Result<*mut leveldb_t, Error> {
+        let mut error = ptr::null_mut();
+        let db = leveldb_open(c_options as *const leveldb_options_t, c_name, &mut error);
+
+        if error.is_null() {
+            Ok(db)
+        } else {
+            Err(Error::new_from_char(error))
         }
     }
 
@@ -65,27 +305,96 @@ impl Database {
     ///
     /// If the database is missing, the behaviour depends on `options.create_if_missing`.
     /// The database will be created using the settings given in `options`.
+    ///
+    /// Opening a database that is already open in this process fails
+    /// immediately, see `Error::is_already_open`.
     pub fn open(name: &Path, options: &Options) -> Result<Database, Error> {
-        let mut error = ptr::null_mut();
+        let open_path = OpenPath::register(name)?;
+        let (db, repaired) = unsafe { Database::open_raw(name, options, None)? };
 
-        unsafe {
-            let c_string = CString::new(name.to_str().unwrap()).unwrap();
-            let c_options = c_options(options, None);
-            let db = leveldb_open(
-                c_options as *const leveldb_options_t,
-                c_string.as_bytes_with_nul().as_ptr() as *const c_char,
-                &mut error,
-            );
-            leveldb_options_destroy(c_options);
+        Ok(Database::new(
+            db,
+            open_path,
+            name,
+            options.clone(),
+            None,
+            repaired,
+        ))
+    }
 
-            if error.is_null() {
-                Ok(Database::new(db, None))
-            } else {
-                Err(Error::new_from_char(error))
+    /// Open a database, retrying while its lock is held
+    ///
+    /// Opening is retried up to `retries` times, waiting `backoff` between attempts,
+    /// as long as it fails because the lock is held (see `Error::is_lock_error`).
+    /// Any other error is returned immediately, as is the last lock error once
+    /// all attempts failed.
+    pub fn open_with_retry(
+        name: &Path,
+        options: &Options,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Database, Error> {
+        let mut attempt = 0;
+
+        loop {
+            match Database::open(name, options) {
+                Err(e) if e.is_lock_error() && attempt < retries => {
+                    attempt += 1;
+                    thread::sleep(backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Open a database, waiting up to `options.open_timeout` for its lock
+    ///
+    /// While the lock is held (see `Error::is_lock_error`) opening is retried
+    /// until the timeout expires, any other error is returned immediately.
+    ///
+    /// A `LOCK` file left behind by a crashed process doesn't need to be
+    /// removed: leveldb locks it with `fcntl`, and that lock is released when
+    /// its holder exits, so only a live handle keeps the database locked.
+    pub fn open_with(name: &Path, options: &OpenOptions) -> Result<Database, Error> {
+        let deadline = options.open_timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            match Database::open(name, &options.options) {
+                Err(e) if e.is_lock_error() => {
+                    let remaining = match deadline {
+                        Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                        None => Duration::from_secs(0),
+                    };
+                    if remaining == Duration::from_secs(0) {
+                        return Err(e);
+                    }
+                    thread::sleep(remaining.min(OPEN_POLL_INTERVAL));
+                }
+                result => return result,
             }
         }
     }
 
+    /// Open a database, creating it if it is missing
+    ///
+    /// `options.create_if_missing` is ignored. The returned flag is true if the
+    /// database was created by this call, i.e. `name` held no database before.
+    /// This is checked while the database is registered as open in this process,
+    /// so concurrent calls from this process can't both see it as created.
+    pub fn open_or_create(name: &Path, options: &Options) -> Result<(Database, bool), Error> {
+        let open_path = OpenPath::register(name)?;
+        let created = !name.join("CURRENT").exists();
+
+        let mut options = options.clone();
+        options.create_if_missing = true;
+        let (db, repaired) = unsafe { Database::open_raw(name, &options, None)? };
+
+        Ok((
+            Database::new(db, open_path, name, options, None, repaired),
+            created,
+        ))
+    }
+
     /// Open a new database with a custom comparator
     ///
     /// If the database is missing, the behaviour depends on `options.create_if_missing`.
@@ -99,24 +408,87 @@ impl Database {
         options: &Options,
         comparator: C,
     ) -> Result<Database, Error> {
-        let mut error = ptr::null_mut();
-        let comp_ptr = create_comparator(Box::new(comparator));
-        unsafe {
-            let c_string = CString::new(name.to_str().unwrap()).unwrap();
-            let c_options = c_options(options, Some(comp_ptr));
-            let db = leveldb_open(
-                c_options as *const leveldb_options_t,
-                c_string.as_bytes_with_nul().as_ptr() as *const c_char,
-                &mut error,
-            );
-            leveldb_options_destroy(c_options);
+        let open_path = OpenPath::register(name)?;
+        let comparator = RawComparator::new(comparator);
+        let (db, repaired) = unsafe { Database::open_raw(name, options, Some(&comparator))? };
 
-            if error.is_null() {
-                Ok(Database::new(db, Some(comp_ptr)))
-            } else {
-                Err(Error::new_from_char(error))
+        Ok(Database::new(
+            db,
+            open_path,
+            name,
+            options.clone(),
+            Some(comparator),
+            repaired,
+        ))
+    }
+
+    /// Read the name of the comparator the database at `name` was created with
+    ///
+    /// The name is taken from the current manifest, without opening the database.
+    /// Databases created without a custom comparator report
+    /// `leveldb.BytewiseComparator`.
+    pub fn stored_comparator_name(name: &Path) -> Result<String, Error> {
+        let current = fs::read_to_string(name.join("CURRENT"))?;
+        let manifest = fs::read(name.join(current.trim_end()))?;
+
+        for record in manifest_records(&manifest)? {
+            if let Some(comparator) = VersionEdit::decode(&record)?.comparator {
+                return Ok(comparator);
             }
         }
+        Err(Error::new(format!(
+            "Corruption: no comparator recorded in {}",
+            current.trim_end()
+        )))
+    }
+
+    /// Close the database and open it again at the same path
+    ///
+    /// The database is reopened with the options and comparator it was opened
+    /// with, except that `error_if_exists` is ignored. The current handle is
+    /// closed before the new one is opened, which makes leveldb re-read its
+    /// MANIFEST, e.g. after external tools touched the directory.
+    pub fn reopen(self) -> Result<Database, Error> {
+        let Database {
+            database,
+            open_path,
+            comparator,
+            path,
+            options,
+            ..
+        } = self;
+        drop(database);
+
+        let mut reopen_options = options.clone();
+        reopen_options.error_if_exists = false;
+        let (db, repaired) =
+            unsafe { Database::open_raw(&path, &reopen_options, comparator.as_ref())? };
+
+        Ok(Database::new(
+            db, open_path, &path, options, comparator, repaired,
+        ))
+    }
+
+    /// Whether the database was repaired while opening it
+    ///
+    /// True if opening failed because of corruption and `Options::auto_repair`
+    /// repaired the database before opening it again. Repairing may have
+    /// dropped data, callers can check this to report or verify it.
+    pub fn was_repaired(&self) -> bool {
+        self.repaired
+    }
+
+    /// Close the database, reporting whether the final sync succeeded
+    ///
+    /// leveldb's close itself can't fail, so before closing the handle the
+    /// write-ahead log is synced to disk by writing an empty batch with
+    /// `sync` set. The handle is closed even if that sync fails. Dropping
+    /// the database closes it as well, but without a sync or error reporting.
+    pub fn close(self) -> Result<(), Error> {
+        let mut options = WriteOptions::new();
+        options.sync = true;
+
+        self.write(&options, &WriteBatch::new())
     }
 
     pub fn put(
@@ -148,7 +520,7 @@ impl Database {
             if error.is_null() {
                 Ok(())
             } else {
-                Err(Error::new_from_char(error))
+                Err(Error::new_from_char(error).for_key(key))
             }
         }
     }
@@ -162,30 +534,183 @@ impl Database {
     }
 
     pub fn get_u8(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.get_bytes(options, key)
+            .map(|bytes_opt| bytes_opt.map(|val| val.into()))
+    }
+
+    /// Store `value` for a key of the key space `T`
+    pub fn put_keyed<T: KeyTag>(
+        &self,
+        options: &WriteOptions,
+        key: &TypedKey<T>,
+        value: &[u8],
+    ) -> Result<(), Error> {
+        self.put_u8(options, key.as_bytes(), value)
+    }
+
+    /// Read the value stored for a key of the key space `T`
+    pub fn get_keyed<T: KeyTag>(
+        &self,
+        options: &ReadOptions,
+        key: &TypedKey<T>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.get_u8(options, key.as_bytes())
+    }
+
+    /// Store a UTF-8 string value for `key`
+    pub fn put_str(&self, options: &WriteOptions, key: &[u8], value: &str) -> Result<(), Error> {
+        self.put_u8(options, key, value.as_bytes())
+    }
+
+    /// Read the value stored for `key` as a UTF-8 string
+    ///
+    /// Returns an error if the stored value is not valid UTF-8.
+    pub fn get_str(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<String>, Error> {
+        match self.get_u8(options, key)? {
+            Some(value) => String::from_utf8(value)
+                .map(Some)
+                .map_err(|e| Error::new(format!("value is not valid UTF-8: {}", e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Read the values of several keys without copying them
+    ///
+    /// The values are kept in the buffers allocated by leveldb, see `ValueBundle`.
+    pub fn get_bundle(&self, options: &ReadOptions, keys: &[&[u8]]) -> Result<ValueBundle, Error> {
+        keys.iter()
+            .map(|key| self.get_bytes(options, key))
+            .collect::<Result<Vec<_>, Error>>()
+            .map(ValueBundle::new)
+    }
+
+    /// Read the values of several keys through a single iterator
+    ///
+    /// The keys are looked up in bytewise order, so that lookups of keys close to
+    /// each other reuse the blocks the iterator already loaded. The values are
+    /// returned in the order of `keys`. This pays off for clustered keys, for
+    /// scattered keys separate `get` calls are as fast.
+    pub fn get_sorted(
+        &self,
+        options: &ReadOptions,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<Vec<u8>>>, Error> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by_key(|&i| keys[i]);
+
+        let iter = self.iter(options);
+        let mut values = vec![None; keys.len()];
+        for i in order {
+            iter.seek(keys[i]);
+            let found = unsafe {
+                leveldb_iter_valid(iter.raw_iterator()) != 0
+                    && raw_key(iter.raw_iterator()) == keys[i]
+            };
+            if found {
+                values[i] = Some(iter.value());
+            }
+        }
+        iter.status()?;
+
+        Ok(values)
+    }
+
+    /// Read the value stored for `key` into a buffer taken from `pool`
+    ///
+    /// The buffer is returned to the pool once the `PooledBuf` is dropped, which
+    /// avoids allocating a new `Vec` for every read.
+    pub fn get_pooled<'p>(
+        &self,
+        options: &ReadOptions,
+        key: &[u8],
+        pool: &'p BufferPool,
+    ) -> Result<Option<PooledBuf<'p>>, Error> {
+        self.get_bytes(options, key)
+            .map(|bytes_opt| bytes_opt.map(|val| pool.fill(&val)))
+    }
+
+    /// Return the length of the value stored for `key`
+    ///
+    /// The value is read and released again without copying it into a `Vec`.
+    /// Returns `None` if the key is missing, an empty value yields `Some(0)`.
+    pub fn value_len(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<usize>, Error> {
+        self.get_bytes(options, key)
+            .map(|bytes_opt| bytes_opt.map(|val| val.len()))
+    }
+
+    /// Read `key` with the default read options
+    ///
+    /// Like `get_u8` with `default_read_options`, but without creating and
+    /// destroying leveldb read options for every call: the database creates
+    /// them once when opened. They are never changed afterwards, so concurrent
+    /// reads from several threads share them safely.
+    pub fn get_default(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        unsafe { self.get_raw(self.default_read_options.ptr, key) }
+            .map(|bytes_opt| bytes_opt.map(|val| val.into()))
+    }
+
+    fn get_bytes(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Bytes>, Error> {
         unsafe {
-            let mut error = ptr::null_mut();
-            let mut length: size_t = 0;
-            let c_readoptions = c_readoptions(options);
-            let result = leveldb_get(
-                self.database.ptr,
-                c_readoptions,
-                key.as_ptr() as *mut c_char,
-                key.len() as size_t,
-                &mut length,
-                &mut error,
-            );
+            let c_readoptions = c_readoptions(&self.effective_read_options(options));
+            let result = self.get_raw(c_readoptions, key);
             leveldb_readoptions_destroy(c_readoptions);
 
-            if error.is_null() {
-                let bytes_opt = Bytes::from_raw(result as *mut u8, length);
+            result
+        }
+    }
 
-                Ok(bytes_opt.map(|val| val.into()))
-            } else {
-                Err(Error::new_from_char(error))
-            }
+    unsafe fn get_raw(
+        &self,
+        c_readoptions: *const leveldb_readoptions_t,
+        key: &[u8],
+    ) -> Result<Option<Bytes>, Error> {
+        let mut error = ptr::null_mut();
+        let mut length: size_t = 0;
+        let result = leveldb_get(
+            self.database.ptr,
+            c_readoptions,
+            key.as_ptr() as *mut c_char,
+            key.len() as size_t,
+            &mut length,
+            &mut error,
+        );
+
+        if error.is_null() {
+            Ok(Bytes::from_raw(result as *mut u8, length))
+        } else {
+            Err(Error::new_from_char(error).for_key(key))
         }
     }
 
+    /// Resolve the read options used for a read, applying the database defaults
+    ///
+    /// Checksums are verified if `options` asks for it, or if the database was
+    /// opened with `Options::default_verify_checksums` and `options` doesn't
+    /// clear `use_database_default`.
+    pub fn effective_read_options(&self, options: &ReadOptions) -> ReadOptions {
+        let default = options.use_database_default && self.options.default_verify_checksums;
+        ReadOptions {
+            verify_checksums: options.verify_checksums || default,
+            ..*options
+        }
+    }
+
+    /// The read options used when no explicit settings are given
+    ///
+    /// These are the `ReadOptions` defaults combined with the defaults the
+    /// database was opened with.
+    pub fn default_read_options(&self) -> ReadOptions {
+        self.effective_read_options(&ReadOptions::new())
+    }
+
+    /// Call `f` with the default read options of this database
+    pub fn with_read_options<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&ReadOptions) -> R,
+    {
+        f(&self.default_read_options())
+    }
+
     pub fn delete(&self, options: &WriteOptions, key: &dyn IntoLevelDBKey) -> Result<(), Error> {
         key.as_u8_slice_for_write(&|k| self.delete_u8(options, k))
     }
@@ -208,10 +733,682 @@ impl Database {
             if error.is_null() {
                 Ok(())
             } else {
-                Err(Error::new_from_char(error))
+                Err(Error::new_from_char(error).for_key(key))
+            }
+        }
+    }
+
+    /// Read-modify-write a single key
+    ///
+    /// Reads the current value of `key` (or `None`), passes it to `f` and writes
+    /// the result back, deleting the key if `f` returns `None`.
+    ///
+    /// This is not atomic: a concurrent write to the same key between the read
+    /// and the write is silently overwritten. Synchronize writers externally if
+    /// that matters.
+    pub fn modify<F>(
+        &self,
+        read_options: &ReadOptions,
+        write_options: &WriteOptions,
+        key: &[u8],
+        f: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(Option<Vec<u8>>) -> Option<Vec<u8>>,
+    {
+        match f(self.get_u8(read_options, key)?) {
+            Some(value) => self.put_u8(write_options, key, &value),
+            None => self.delete_u8(write_options, key),
+        }
+    }
+
+    /// Write `value` for `key`, returning the value it replaced
+    ///
+    /// Like `modify`, this is not atomic: a concurrent write between the read
+    /// and the write is overwritten and not reflected in the returned value.
+    ///
+    /// The previous value is read with a plain get, without a snapshot: a
+    /// single get already sees one consistent state, and a snapshot would
+    /// not close the window between the read and the write either.
+    pub fn put_returning(
+        &self,
+        read_options: &ReadOptions,
+        write_options: &WriteOptions,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let previous = self.get_u8(read_options, key)?;
+        self.put_u8(write_options, key, value)?;
+        Ok(previous)
+    }
+
+    /// Delete `key`, returning whether it was present
+    ///
+    /// This is two operations, a read followed by a delete, and not atomic:
+    /// a concurrent write between them is deleted, but not reflected in the
+    /// returned value. Absent keys are not deleted, so no write happens.
+    ///
+    /// Existence is checked with a plain get, without a snapshot: a single
+    /// get already sees one consistent state, and a snapshot would not close
+    /// the window between the read and the delete either.
+    pub fn delete_existing(
+        &self,
+        read_options: &ReadOptions,
+        write_options: &WriteOptions,
+        key: &[u8],
+    ) -> Result<bool, Error> {
+        if self.get_bytes(read_options, key)?.is_none() {
+            return Ok(false);
+        }
+        self.delete_u8(write_options, key)?;
+        Ok(true)
+    }
+
+    /// Delete a set of keys atomically
+    ///
+    /// All deletes are collected into a single `WriteBatch`, so either all keys
+    /// are deleted or, on error, none of them.
+    pub fn delete_many<K, I>(&self, options: &WriteOptions, keys: I) -> Result<(), Error>
+    where
+        K: IntoLevelDBKey,
+        I: IntoIterator<Item = K>,
+    {
+        let batch = WriteBatch::new();
+
+        for key in keys {
+            batch.delete(&key);
+        }
+
+        self.write(options, &batch)
+    }
+
+    /// Load a large number of entries in one go
+    ///
+    /// All entries are collected into a single `WriteBatch` and written at once,
+    /// which avoids a log write per entry. leveldb doesn't allow skipping the
+    /// write-ahead log, so to reduce IO further open the database with a large
+    /// `Options::write_buffer_size` for the duration of the import.
+    ///
+    /// Returns the number of entries written.
+    pub fn bulk_load<K, V, I>(&self, options: &WriteOptions, entries: I) -> Result<u64, Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let batch = WriteBatch::new();
+        let mut count = 0;
+
+        for (key, value) in entries {
+            batch.put_u8(key.as_ref(), value.as_ref());
+            count += 1;
+        }
+        self.write(options, &batch)?;
+
+        Ok(count)
+    }
+
+    /// Read up to `limit` entries of the range `[start, end)`
+    ///
+    /// Keys are compared bytewise. Returns fewer entries if the range is
+    /// exhausted, `start > end` is reported as an error.
+    pub fn scan_range(
+        &self,
+        options: &ReadOptions,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Vec<Entry>, Error> {
+        if start > end {
+            return Err(Error::new(
+                "range start is greater than range end".to_string(),
+            ));
+        }
+
+        let mut entries = Vec::with_capacity(limit);
+        if limit == 0 {
+            return Ok(entries);
+        }
+
+        let mut iter = self.iter(options).from(start);
+        while let Some((key, value)) = iter.try_next()? {
+            if key.as_slice() >= end {
+                break;
+            }
+            entries.push((key, value));
+            if entries.len() == limit {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Collect the keys starting with `prefix`, in order
+    ///
+    /// Only keys are copied out of leveldb, values are never read.
+    pub fn prefix_keys(&self, options: &ReadOptions, prefix: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        let mut iter = self.keys_iter(options).prefix(prefix);
+
+        let mut keys = Vec::new();
+        while let Some(key) = iter.try_next()? {
+            keys.push(key);
+        }
+
+        Ok(keys)
+    }
+
+    /// Find the last entry of the range `[start, end)` matching `pred`
+    ///
+    /// The range is scanned backwards from `end`, stopping at the first match.
+    /// Keys are compared bytewise, the predicate sees the key and value without
+    /// them being copied.
+    pub fn find_last<F: Fn(&[u8], &[u8]) -> bool>(
+        &self,
+        options: &ReadOptions,
+        start: &[u8],
+        end: &[u8],
+        pred: F,
+    ) -> Result<Option<Entry>, Error> {
+        let iter = self.iter(options);
+        let raw = iter.raw_iterator();
+
+        unsafe {
+            // position on the last key before `end`
+            iter.seek(end);
+            if leveldb_iter_valid(raw) != 0 {
+                leveldb_iter_prev(raw);
+            } else {
+                leveldb_iter_seek_to_last(raw);
+            }
+
+            while leveldb_iter_valid(raw) != 0 {
+                let key = raw_key(raw);
+                if key < start {
+                    break;
+                }
+                let value = raw_value(raw);
+                if key < end && pred(key, value) {
+                    return Ok(Some((key.to_vec(), value.to_vec())));
+                }
+                leveldb_iter_prev(raw);
+            }
+        }
+        iter.status()?;
+
+        Ok(None)
+    }
+
+    /// Load the blocks of the range `[start, end)` into the block cache
+    ///
+    /// The range is scanned with `fill_cache` set, regardless of `options`, while
+    /// only the keys are looked at. Keys are compared bytewise. leveldb has no
+    /// way to report cache hits, so the effect only shows in read latencies.
+    ///
+    /// Returns the number of entries in the range.
+    pub fn warm_range(
+        &self,
+        options: &ReadOptions,
+        start: &[u8],
+        end: &[u8],
+    ) -> Result<usize, Error> {
+        let options = ReadOptions {
+            fill_cache: true,
+            ..*options
+        };
+        let mut keys = self.keys_iter(&options).from(start);
+        let mut count = 0;
+
+        while keys.advance(false) {
+            if unsafe { raw_key(keys.raw_iterator()) } >= end {
+                break;
+            }
+            count += 1;
+        }
+        keys.status()?;
+
+        Ok(count)
+    }
+
+    /// Approximate the file system space used by the keys in each `[start, limit)` range
+    ///
+    /// Data that was not yet compacted into table files is not taken into account.
+    pub fn approximate_sizes(&self, ranges: &[(&[u8], &[u8])]) -> Vec<u64> {
+        let starts: Vec<*const c_char> = ranges.iter().map(|r| r.0.as_ptr() as _).collect();
+        let start_lens: Vec<size_t> = ranges.iter().map(|r| r.0.len()).collect();
+        let limits: Vec<*const c_char> = ranges.iter().map(|r| r.1.as_ptr() as _).collect();
+        let limit_lens: Vec<size_t> = ranges.iter().map(|r| r.1.len()).collect();
+        let mut sizes = vec![0u64; ranges.len()];
+
+        unsafe {
+            leveldb_approximate_sizes(
+                self.database.ptr,
+                ranges.len() as c_int,
+                starts.as_ptr(),
+                start_lens.as_ptr(),
+                limits.as_ptr(),
+                limit_lens.as_ptr(),
+                sizes.as_mut_ptr(),
+            );
+        }
+
+        sizes
+    }
+
+    /// Split the keys of `snapshot` into up to `n` ranges of similar size
+    ///
+    /// Returns `[start, end)` ranges, with keys compared bytewise, that together
+    /// cover all keys without overlapping. The ranges can be scanned in parallel,
+    /// each with an iterator of its own. Sizes are estimated using
+    /// `approximate_sizes`, if all data is still held in memory the keys are
+    /// counted instead. Fewer ranges are returned if there are too few keys.
+    pub fn par_ranges(&self, snapshot: &Snapshot<'_>, n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let options = ReadOptions::scan();
+        let first = match snapshot.keys_iter(&options).next() {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        let mut end = snapshot.keys_iter(&options).last().unwrap_or_default();
+        // the smallest key after the last one
+        end.push(0);
+
+        let n = n.max(1);
+        let total = self.approximate_sizes(&[(&first, &end)])[0];
+        let mut bounds = vec![first.clone()];
+
+        if total > 0 {
+            for i in 1..n {
+                let target = total * i as u64 / n as u64;
+                bounds.push(self.split_key(&first, &end, target));
+            }
+        } else {
+            let mut keys = snapshot.keys_iter(&options);
+            let mut count = 0;
+            while keys.advance(false) {
+                count += 1;
+            }
+
+            let mut keys = snapshot.keys_iter(&options);
+            let mut pos = 0;
+            for i in 1..n {
+                let index = count * i / n;
+                if index < pos {
+                    continue;
+                }
+                if let Some(key) = keys.nth(index - pos) {
+                    bounds.push(key);
+                }
+                pos = index + 1;
             }
         }
+
+        bounds.push(end);
+        bounds.sort();
+        bounds.dedup();
+        bounds
+            .windows(2)
+            .map(|w| (w[0].clone(), w[1].clone()))
+            .collect()
     }
+
+    // bisect for the key splitting off `target` bytes from the start of the range
+    fn split_key(&self, start: &[u8], end: &[u8], target: u64) -> Vec<u8> {
+        let mut low = start.to_vec();
+        let mut high = end.to_vec();
+
+        for _ in 0..SPLIT_BISECT_STEPS {
+            let mid = key_midpoint(&low, &high);
+            if mid <= low || mid >= high {
+                break;
+            }
+            if self.approximate_sizes(&[(start, &mid)])[0] < target {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        high
+    }
+
+    /// Materialize all entries into a `HashMap`
+    ///
+    /// Mostly useful to compare the contents of a database in tests.
+    pub fn to_hashmap(&self, options: &ReadOptions) -> HashMap<Vec<u8>, Vec<u8>> {
+        self.iter(options).collect()
+    }
+
+    /// Hash the logical contents of the database
+    ///
+    /// All entries are folded in key order through a 64 bit FNV-1a hash as
+    /// key length, key, value length and value, with lengths as big-endian `u64`.
+    /// The result only depends on the entries, not on how they are laid out on
+    /// disk, so databases with equal contents hash equal.
+    pub fn content_hash(&self, options: &ReadOptions) -> u64 {
+        self.iter(options)
+            .fold(FNV_OFFSET_BASIS, |hash, (key, value)| {
+                let hash = fnv1a(hash, &(key.len() as u64).to_be_bytes());
+                let hash = fnv1a(hash, &key);
+                let hash = fnv1a(hash, &(value.len() as u64).to_be_bytes());
+                fnv1a(hash, &value)
+            })
+    }
+
+    /// Export all entries of the database to a writer
+    ///
+    /// Every entry is written as a length-prefixed frame: the key length as a
+    /// big-endian `u32`, the key, the value length as a big-endian `u32` and the value.
+    /// The dump is independent of the on-disk format and can be read back using `import`.
+    ///
+    /// Returns the number of entries written.
+    pub fn export<W: Write>(&self, options: &ReadOptions, out: &mut W) -> Result<u64, Error> {
+        let mut count = 0;
+
+        // a corrupted entry fails the export instead of ending the dump early
+        let mut iter = self.iter(options);
+        while let Some((key, value)) = iter.try_next()? {
+            write_frame(out, &key)?;
+            write_frame(out, &value)?;
+            count += 1;
+        }
+        out.flush()?;
+
+        Ok(count)
+    }
+
+    /// Import entries written by `export` from a reader
+    ///
+    /// All entries are collected into a single `WriteBatch`, so either the whole
+    /// dump is applied or, on error, nothing is written.
+    ///
+    /// Returns the number of entries imported.
+    pub fn import<R: Read>(&self, options: &WriteOptions, input: &mut R) -> Result<u64, Error> {
+        let batch = WriteBatch::new();
+        let mut count = 0;
+
+        while let Some(key) = read_frame(input, true)? {
+            let value = read_frame(input, false)?.unwrap_or_default();
+            batch.put_u8(&key, &value);
+            count += 1;
+        }
+        self.write(options, &batch)?;
+
+        Ok(count)
+    }
+
+    /// Query a leveldb property such as `leveldb.stats`
+    ///
+    /// Returns `None` if the property is unknown.
+    pub fn property(&self, name: &str) -> Option<String> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let value = leveldb_property_value(self.database.ptr, c_name.as_ptr());
+            if value.is_null() {
+                return None;
+            }
+            let result = CStr::from_ptr(value).to_string_lossy().into_owned();
+            leveldb_free(value as *mut c_void);
+            Some(result)
+        }
+    }
+
+    /// The approximate number of bytes of memory used by the database
+    ///
+    /// Returns `None` if leveldb doesn't report it.
+    pub fn approximate_memory_usage(&self) -> Option<u64> {
+        self.property("leveldb.approximate-memory-usage")
+            .and_then(|usage| usage.trim().parse().ok())
+    }
+
+    /// Read the lines leveldb wrote to its info log so far
+    ///
+    /// leveldb logs recoveries, flushes and compactions to the `LOG` file in the
+    /// database directory, or to `Options::info_log_path`. Its C API can't
+    /// create a logger, so the messages can't be routed to a callback and are
+    /// read from that file instead. The log of the previous session is kept
+    /// with `.old` appended to the name and not included.
+    pub fn info_log(&self) -> Result<Vec<String>, Error> {
+        let log = fs::read(self.info_log_file())?;
+
+        Ok(String::from_utf8_lossy(&log)
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    // the file leveldb writes its info log to
+    pub(crate) fn info_log_file(&self) -> PathBuf {
+        match self.options.info_log_path {
+            Some(ref path) => path.clone(),
+            None => self.path.join("LOG"),
+        }
+    }
+
+    /// Collect the database statistics in one call
+    pub fn stats(&self) -> DbStats {
+        let num_files_per_level = (0..NUM_LEVELS)
+            .map_while(|level| {
+                self.property(&format!("leveldb.num-files-at-level{}", level))
+                    .and_then(|files| files.trim().parse().ok())
+            })
+            .collect();
+
+        DbStats {
+            num_files_per_level,
+            approximate_memory_usage: self.approximate_memory_usage(),
+            stats: self.property("leveldb.stats").unwrap_or_default(),
+            sstables: self.property("leveldb.sstables").unwrap_or_default(),
+        }
+    }
+}
+
+const LOG_BLOCK_SIZE: usize = 32 * 1024;
+const LOG_HEADER_SIZE: usize = 7;
+
+/// Move the info log leveldb is writing at `log` to `dest`, keeping a file
+/// found at `dest` like leveldb keeps the log of the previous session.
+fn move_info_log(log: &Path, dest: &Path) -> std::io::Result<()> {
+    if dest.exists() {
+        let mut old = dest.as_os_str().to_owned();
+        old.push(".old");
+        fs::rename(dest, old)?;
+    }
+    fs::rename(log, dest)
+}
+
+/// Reassemble the records of a leveldb log file, such as the manifest.
+///
+/// Like leveldb's log reader, a record cut off at the end of the data is
+/// treated as the end of the log: it is still being appended, e.g. while a
+/// manifest is copied during a compaction.
+pub(crate) fn manifest_records(data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let corrupt = || Error::new("Corruption: truncated manifest record".to_string());
+
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    for (index, block) in data.chunks(LOG_BLOCK_SIZE).enumerate() {
+        let last_block = (index + 1) * LOG_BLOCK_SIZE >= data.len();
+        let mut pos = 0;
+        while pos + LOG_HEADER_SIZE <= block.len() {
+            let len = u16::from_le_bytes([block[pos + 4], block[pos + 5]]) as usize;
+            let kind = block[pos + 6];
+            let start = pos + LOG_HEADER_SIZE;
+            let fragment = match block.get(start..start + len) {
+                Some(fragment) => fragment,
+                None if last_block => return Ok(records),
+                None => return Err(corrupt()),
+            };
+            pos = start + len;
+
+            match kind {
+                // zero padding at the end of a block
+                0 => break,
+                // a full record, or the first fragment of one
+                1 | 2 => record = fragment.to_vec(),
+                _ => record.extend_from_slice(fragment),
+            }
+            if kind == 1 || kind == 4 {
+                records.push(std::mem::take(&mut record));
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// The parts of an encoded version edit read by this crate.
+#[derive(Default)]
+pub(crate) struct VersionEdit {
+    pub(crate) comparator: Option<String>,
+    pub(crate) log_number: Option<u64>,
+    pub(crate) prev_log_number: Option<u64>,
+    pub(crate) deleted_files: Vec<u64>,
+    pub(crate) new_files: Vec<u64>,
+}
+
+impl VersionEdit {
+    /// Decode a version edit as stored in the records of a manifest.
+    pub(crate) fn decode(mut edit: &[u8]) -> Result<VersionEdit, Error> {
+        fn varint(input: &mut &[u8]) -> Result<u64, Error> {
+            let mut value = 0u64;
+            for shift in (0..64).step_by(7) {
+                let (&byte, rest) = input
+                    .split_first()
+                    .ok_or_else(|| Error::new("Corruption: truncated version edit".to_string()))?;
+                *input = rest;
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err(Error::new(
+                "Corruption: invalid varint in version edit".to_string(),
+            ))
+        }
+        fn slice<'a>(input: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+            let len = varint(input)? as usize;
+            if input.len() < len {
+                return Err(Error::new("Corruption: truncated version edit".to_string()));
+            }
+            let (bytes, rest) = input.split_at(len);
+            *input = rest;
+            Ok(bytes)
+        }
+
+        let mut decoded = VersionEdit::default();
+        while !edit.is_empty() {
+            match varint(&mut edit)? {
+                1 => {
+                    let comparator = slice(&mut edit)?;
+                    decoded.comparator = Some(String::from_utf8_lossy(comparator).into_owned());
+                }
+                2 => decoded.log_number = Some(varint(&mut edit)?),
+                9 => decoded.prev_log_number = Some(varint(&mut edit)?),
+                3 | 4 => {
+                    varint(&mut edit)?;
+                }
+                5 => {
+                    varint(&mut edit)?;
+                    slice(&mut edit)?;
+                }
+                6 => {
+                    // level, file number
+                    varint(&mut edit)?;
+                    decoded.deleted_files.push(varint(&mut edit)?);
+                }
+                7 => {
+                    // level, file number, file size, smallest and largest key
+                    varint(&mut edit)?;
+                    decoded.new_files.push(varint(&mut edit)?);
+                    varint(&mut edit)?;
+                    slice(&mut edit)?;
+                    slice(&mut edit)?;
+                }
+                tag => {
+                    return Err(Error::new(format!(
+                        "Corruption: unknown version edit tag {}",
+                        tag
+                    )))
+                }
+            }
+        }
+        Ok(decoded)
+    }
+}
+
+const SPLIT_BISECT_STEPS: usize = 64;
+
+/// The key halfway between `low` and `high`, read as big-endian numbers after
+/// padding both with zeros to the same length.
+fn key_midpoint(low: &[u8], high: &[u8]) -> Vec<u8> {
+    let len = low.len().max(high.len()) + 1;
+    let byte = |key: &[u8], i: usize| key.get(i).copied().unwrap_or(0) as u16;
+
+    let mut sum = vec![0u16; len];
+    let mut carry = 0;
+    for i in (0..len).rev() {
+        let s = byte(low, i) + byte(high, i) + carry;
+        sum[i] = s & 0xff;
+        carry = s >> 8;
+    }
+
+    let mut mid = vec![0u8; len];
+    let mut rem = carry;
+    for i in 0..len {
+        let v = (rem << 8) | sum[i];
+        mid[i] = (v / 2) as u8;
+        rem = v % 2;
+    }
+
+    mid
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter()
+        .fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+fn write_frame<W: Write>(out: &mut W, data: &[u8]) -> Result<(), Error> {
+    if data.len() > u32::MAX as usize {
+        return Err(Error::new(format!(
+            "entry of {} bytes exceeds the maximum frame size",
+            data.len()
+        )));
+    }
+    out.write_all(&(data.len() as u32).to_be_bytes())
+        .and_then(|_| out.write_all(data))
+        .map_err(Error::from)
+}
+
+/// Read a single length-prefixed frame.
+///
+/// Returns `None` if the reader is exhausted before the first byte of the frame
+/// and `at_boundary` is set, any other short read is reported as an error.
+fn read_frame<R: Read>(input: &mut R, at_boundary: bool) -> Result<Option<Vec<u8>>, Error> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+
+    while filled < len.len() {
+        match input.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 && at_boundary => return Ok(None),
+            Ok(0) => return Err(Error::new("unexpected end of import data".to_string())),
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // the length is untrusted, only allocate for the data actually read
+    let len = u32::from_be_bytes(len) as u64;
+    let mut data = Vec::new();
+    input.by_ref().take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(Error::new("unexpected end of import data".to_string()));
+    }
+
+    Ok(Some(data))
 }
 
 /// Helpful when building interfaces that read from either DB or Snapshot
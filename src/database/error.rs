@@ -1,20 +1,43 @@
 //! The module defining custom leveldb error type.
 
+use super::bytes::DisplayKey;
 use leveldb_sys::leveldb_free;
 use libc::{c_char, c_void};
 use std;
+use std::io;
+use std::path::Path;
 
-/// A leveldb error, just containing the error string
-/// provided by leveldb.
+const ALREADY_OPEN_MESSAGE: &str = "database already open in this process: ";
+const CONFLICT_MESSAGE: &str = "Conflict: key changed since the transaction's snapshot: ";
+const DUPLICATE_KEY_MESSAGE: &str = "key added to the batch twice: ";
+const COMPARATOR_MISMATCH_MESSAGE: &str = " does not match existing comparator : ";
+
+/// The comparators involved in a failed open, see `Error::comparator_mismatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComparatorMismatch {
+    /// Name of the comparator the database was created with
+    pub expected: String,
+    /// Name of the comparator passed when opening it
+    pub found: String,
+}
+
+/// A leveldb error, containing the error string provided by leveldb.
+///
+/// Errors of filesystem operations done by this crate itself keep the
+/// underlying `io::Error` as their `source`.
 #[derive(Debug)]
 pub struct Error {
     message: String,
+    source: Option<io::Error>,
 }
 
 impl Error {
     /// create a new Error, using the String provided
     pub fn new(message: String) -> Error {
-        Error { message }
+        Error {
+            message,
+            source: None,
+        }
     }
 
     /// create an error from a c-string buffer.
@@ -33,6 +56,79 @@ impl Error {
         leveldb_free(message as *mut c_void);
         Error::new(err_string)
     }
+
+    /// Name the key the failed operation was performed on, keeping the
+    /// `source` of the error.
+    pub(crate) fn for_key(self, key: &[u8]) -> Error {
+        Error {
+            message: format!("{} (key {})", self.message, DisplayKey(key)),
+            source: self.source,
+        }
+    }
+
+    pub(crate) fn already_open(path: &Path) -> Error {
+        Error::new(format!("{}{}", ALREADY_OPEN_MESSAGE, path.display()))
+    }
+
+    pub(crate) fn conflict(key: &[u8]) -> Error {
+        Error::new(format!("{}{}", CONFLICT_MESSAGE, DisplayKey(key)))
+    }
+
+    pub(crate) fn duplicate_key(key: &[u8]) -> Error {
+        Error::new(format!("{}{}", DUPLICATE_KEY_MESSAGE, DisplayKey(key)))
+    }
+
+    /// Whether the error reports that the database lock is held, e.g. by
+    /// another process still having the database open.
+    ///
+    /// This includes databases already open in this process, see `is_already_open`.
+    pub fn is_lock_error(&self) -> bool {
+        (self.message.starts_with("IO error: lock ") && self.message.contains("LOCK"))
+            || self.is_already_open()
+    }
+
+    /// Whether the error reports that the database is already open in this process.
+    pub fn is_already_open(&self) -> bool {
+        self.message.starts_with(ALREADY_OPEN_MESSAGE)
+    }
+
+    /// Whether the error reports that a transaction was not committed
+    /// because a key it read was changed, see `Transaction::commit`.
+    pub fn is_conflict(&self) -> bool {
+        self.message.starts_with(CONFLICT_MESSAGE)
+    }
+
+    /// Whether the error reports a key added twice to a batch detecting
+    /// conflicts, see `WriteBatch::with_conflict_detection`.
+    pub fn is_duplicate_key(&self) -> bool {
+        self.message.starts_with(DUPLICATE_KEY_MESSAGE)
+    }
+
+    /// Whether the error reports corrupted data.
+    pub fn is_corruption(&self) -> bool {
+        self.message.starts_with("Corruption: ")
+    }
+
+    /// Whether the error reports that the database already exists while
+    /// opening it with `error_if_exists` set.
+    pub fn is_already_exists(&self) -> bool {
+        self.message.starts_with("Invalid argument: ")
+            && self.message.ends_with("exists (error_if_exists is true)")
+    }
+
+    /// The comparator names, if the error reports that the database was
+    /// opened with a different comparator than it was created with.
+    pub fn comparator_mismatch(&self) -> Option<ComparatorMismatch> {
+        let (expected, found) = self
+            .message
+            .strip_prefix("Invalid argument: ")?
+            .split_once(COMPARATOR_MISMATCH_MESSAGE)?;
+
+        Some(ComparatorMismatch {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        })
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -42,10 +138,18 @@ impl std::fmt::Display for Error {
 }
 
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        &self.message
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
     }
-    fn cause(&self) -> Option<&dyn std::error::Error> {
-        None
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error {
+            message: format!("IO error: {}", err),
+            source: Some(err),
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+//! Structs to work with leveldb filter policies.
+use leveldb_sys::{
+    leveldb_filterpolicy_create_bloom, leveldb_filterpolicy_destroy, leveldb_filterpolicy_t,
+};
+use std::sync::Arc;
+
+#[allow(missing_docs)]
+struct RawFilterPolicy {
+    ptr: *mut leveldb_filterpolicy_t,
+}
+
+unsafe impl Send for RawFilterPolicy {}
+unsafe impl Sync for RawFilterPolicy {}
+
+impl Drop for RawFilterPolicy {
+    fn drop(&mut self) {
+        unsafe {
+            leveldb_filterpolicy_destroy(self.ptr);
+        }
+    }
+}
+
+/// Represents a leveldb filter policy
+///
+/// A filter lets leveldb skip reading table blocks that can't contain a key,
+/// which saves disk reads when looking up keys that don't exist.
+///
+/// Clones refer to the same underlying policy, which is destroyed once the
+/// last clone is dropped. A database must be reopened with the same policy
+/// for its filters to be used.
+#[derive(Clone)]
+pub struct FilterPolicy {
+    raw: Arc<RawFilterPolicy>,
+    bits_per_key: i32,
+}
+
+impl FilterPolicy {
+    /// Create a bloom filter policy using `bits_per_key` bits per key
+    ///
+    /// 10 bits per key yield about 1% false positives.
+    pub fn bloom(bits_per_key: i32) -> FilterPolicy {
+        let policy = unsafe { leveldb_filterpolicy_create_bloom(bits_per_key) };
+        FilterPolicy {
+            raw: Arc::new(RawFilterPolicy { ptr: policy }),
+            bits_per_key,
+        }
+    }
+
+    /// The number of bits per key of the bloom filter
+    pub fn bits_per_key(&self) -> i32 {
+        self.bits_per_key
+    }
+
+    #[allow(missing_docs)]
+    pub fn raw_ptr(&self) -> *mut leveldb_filterpolicy_t {
+        self.raw.ptr
+    }
+}
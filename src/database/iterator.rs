@@ -2,14 +2,20 @@
 //!
 //! Iteration is one of the most important parts of leveldb. This module provides
 //! Iterators to iterate over key, values and pairs of both.
+use super::bytes::{prefix_successor, raw_slice};
+use super::error::Error;
 use super::options::{c_readoptions, ReadOptions};
+use super::util::FromU8;
 use super::Database;
 use crate::database::snapshots::Snapshot;
 use leveldb_sys::*;
 use libc::{c_char, size_t};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::iter;
 use std::marker::PhantomData;
-use std::slice::from_raw_parts;
+use std::ptr;
 
 #[allow(missing_docs)]
 struct RawIterator {
@@ -36,6 +42,7 @@ pub struct Iterator<'a> {
     from: Option<&'a [u8]>,
     to: Option<&'a [u8]>,
     prefix: Option<&'a [u8]>,
+    prefix_end: Option<Vec<u8>>,
 }
 
 /// An iterator over the leveldb keyspace  that browses the keys backwards.
@@ -51,6 +58,7 @@ pub struct RevIterator<'a> {
     from: Option<&'a [u8]>,
     to: Option<&'a [u8]>,
     prefix: Option<&'a [u8]>,
+    prefix_end: Option<Vec<u8>>,
 }
 
 /// An iterator over the leveldb keyspace.
@@ -81,6 +89,155 @@ pub struct RevValueIterator<'a> {
     inner: RevIterator<'a>,
 }
 
+/// An iterator adapter decoding keys using `FromU8`.
+///
+/// Decoding is delegated to `FromU8::from_u8`, so a key that can't be decoded
+/// (e.g. a key of the wrong length for an integer type) panics with a message
+/// naming the offending length.
+pub struct DecodedKeys<I, T> {
+    inner: I,
+    marker: PhantomData<T>,
+}
+
+/// An iterator adapter decoding the keys of (key, value) pairs using `FromU8`.
+///
+/// Values are passed through unchanged. Malformed keys panic, see `DecodedKeys`.
+pub struct DecodedEntries<I, T> {
+    inner: I,
+    marker: PhantomData<T>,
+}
+
+/// An iterator adapter mapping the keys of (key, value) pairs with a function.
+///
+/// The function reads the key straight from leveldb's buffer, the key is only
+/// copied if the function does so.
+pub struct MapKeyIterator<'a, T, F> {
+    inner: Iterator<'a>,
+    f: F,
+    marker: PhantomData<T>,
+}
+
+/// An iterator yielding overlapping pairs of adjacent (key, value) entries.
+pub struct PairsIterator<'a> {
+    inner: Iterator<'a>,
+    prev: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// An iterator yielding the (key, value) pairs of an iterator while their keys
+/// start with a prefix, see `Iterator::take_while_prefix`.
+pub struct TakeWhilePrefix<'b, 'a> {
+    inner: &'b mut Iterator<'a>,
+    prefix: &'b [u8],
+    done: bool,
+}
+
+/// An iterator over the keys starting with a prefix, yielding them with the
+/// prefix removed, see `Iterator::strip_prefix`.
+pub struct StripPrefixIterator<I> {
+    inner: I,
+    prefix_len: usize,
+}
+
+/// An iterator over the (key, value) pairs whose keys match a predicate.
+///
+/// The predicate is checked against leveldb's buffer, entries it rejects are
+/// never copied.
+pub struct FilterKeysIterator<'a, F> {
+    inner: Iterator<'a>,
+    pred: F,
+}
+
+/// An iterator yielding the (key, value) pairs of an iterator in batches of a
+/// fixed size, see `Iterator::chunks`.
+pub struct ChunksIterator<'a> {
+    inner: Iterator<'a>,
+    size: usize,
+}
+
+/// An iterator yielding the first (key, value) pair of a reverse iterator for
+/// each distinct key prefix, see `RevIterator::latest_per_prefix`.
+pub struct LatestPerPrefix<'a> {
+    inner: RevIterator<'a>,
+    prefix_len: usize,
+    last: Option<Vec<u8>>,
+}
+
+/// An iterator yielding the (key, value) pairs of an iterator as `Ok` items,
+/// see `Iterator::results`.
+///
+/// An error of the underlying leveldb iterator is yielded as a final `Err`
+/// item, so that it can't be mistaken for the regular end of the iteration.
+pub struct TryIterator<'a> {
+    inner: Iterator<'a>,
+    done: bool,
+}
+
+/// An iterator over the keys whose values contain a byte pattern, see
+/// `Iterator::values_contain`.
+pub struct ValuesContain<'a> {
+    inner: Iterator<'a>,
+    needle: &'a [u8],
+}
+
+/// An iterator calling a function with every (key, value) pair before
+/// yielding it, see `Iterator::inspect_entry`.
+pub struct InspectIterator<'a, F> {
+    inner: Iterator<'a>,
+    f: F,
+}
+
+/// Which database wins when both databases of a `MergeIterator` hold a key
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Precedence {
+    /// Yield the entry of the first database
+    First,
+    /// Yield the entry of the second database
+    Second,
+}
+
+/// An iterator over the union of two databases in key order, see `merge_iter`.
+pub struct MergeIterator<'a> {
+    // the first database, whose comparator orders the keys of both
+    database: &'a Database,
+    first: iter::Peekable<Iterator<'a>>,
+    second: iter::Peekable<Iterator<'a>>,
+    precedence: Precedence,
+}
+
+/// The position of a scan that can be resumed on another database handle
+///
+/// The scan remembers the last key it yielded. Resuming continues with the
+/// first key after it, even if that key was deleted in the meantime, e.g. to
+/// continue a long scan after the database was reopened. Keys are compared
+/// bytewise.
+#[derive(Clone, Debug, Default)]
+pub struct ResumableScan {
+    last_key: Option<Vec<u8>>,
+}
+
+/// An iterator over the (key, value) pairs following the position of a
+/// `ResumableScan`, advancing it as entries are yielded.
+pub struct ResumedIterator<'s, 'a> {
+    inner: Iterator<'a>,
+    scan: &'s mut ResumableScan,
+    seek_pending: bool,
+}
+
+/// The items of an iterator not consumed yet, borrowing the iterator, see
+/// e.g. `Iterator::remaining`.
+pub struct Remaining<'r, I> {
+    inner: &'r mut I,
+}
+
+/// An iterator yielding every `stride`-th item of a leveldb iterator.
+///
+/// Skipped entries only advance the underlying leveldb iterator, their keys and
+/// values are never copied.
+pub struct SampleIterator<I> {
+    inner: I,
+    stride: usize,
+}
+
 /// A trait to allow access to the three main iteration styles of leveldb.
 pub trait Iterable<'a> {
     /// Return an Iterator iterating over (Key,Value) pairs
@@ -89,6 +246,19 @@ pub trait Iterable<'a> {
     fn keys_iter(&'a self, options: &ReadOptions) -> KeyIterator<'a>;
     /// Returns an Iterator iterating over Values only.
     fn value_iter(&'a self, options: &ReadOptions) -> ValueIterator<'a>;
+    /// Returns an Iterator over the (Key,Value) pairs starting with `prefix`.
+    ///
+    /// The iterator starts at the prefix and ends as soon as it passes the
+    /// exclusive upper bound computed by `bytes::prefix_successor`.
+    fn prefix_scan(&'a self, options: &ReadOptions, prefix: &'a [u8]) -> Iterator<'a> {
+        self.iter(options).prefix(prefix)
+    }
+    /// Returns an iterator over the (Key,Value) pairs reporting errors.
+    ///
+    /// See `TryIterator`.
+    fn try_iter(&'a self, options: &ReadOptions) -> TryIterator<'a> {
+        self.iter(options).results()
+    }
 }
 
 impl<'a> Iterable<'a> for Database {
@@ -105,6 +275,65 @@ impl<'a> Iterable<'a> for Database {
     }
 }
 
+/// Borrow the key the iterator points at.
+///
+/// # Safety
+/// The iterator must be valid, and the slice must not be used after it moved.
+pub(crate) unsafe fn raw_key<'b>(iter: *mut leveldb_iterator_t) -> &'b [u8] {
+    let length: size_t = 0;
+    let key = leveldb_iter_key(iter, &length);
+    raw_slice(key, length)
+}
+
+/// Borrow the value the iterator points at.
+///
+/// # Safety
+/// The iterator must be valid, and the slice must not be used after it moved.
+pub(crate) unsafe fn raw_value<'b>(iter: *mut leveldb_iterator_t) -> &'b [u8] {
+    let length: size_t = 0;
+    let value = leveldb_iter_value(iter, &length);
+    raw_slice(value, length)
+}
+
+/// Seek to the last key at or below the inclusive `upper` bound, or else
+/// below the exclusive `prefix_end`.
+///
+/// # Safety
+/// The iterator must not have been destroyed.
+unsafe fn seek_to_last_within(
+    iter: *mut leveldb_iterator_t,
+    upper: Option<&[u8]>,
+    prefix_end: Option<&[u8]>,
+) {
+    if let Some(k) = upper {
+        // the bound is inclusive, step back if it doesn't exist
+        leveldb_iter_seek(iter, k.as_ptr() as *mut c_char, k.len() as size_t);
+        if leveldb_iter_valid(iter) == 0 {
+            leveldb_iter_seek_to_last(iter);
+        } else if raw_key(iter) > k {
+            leveldb_iter_prev(iter);
+        }
+    } else if let Some(end) = prefix_end {
+        leveldb_iter_seek(iter, end.as_ptr() as *mut c_char, end.len() as size_t);
+        if leveldb_iter_valid(iter) == 0 {
+            leveldb_iter_seek_to_last(iter);
+        } else {
+            leveldb_iter_prev(iter);
+        }
+    } else {
+        leveldb_iter_seek_to_last(iter);
+    }
+}
+
+// The exclusive upper bound of the prefix of `iter`, derived from the prefix
+// if the iterator doesn't store it
+fn prefix_end<'a, 'i, I: LevelDBIterator<'a> + ?Sized>(iter: &'i I) -> Option<Cow<'i, [u8]>> {
+    match iter.prefix_end_key() {
+        Some(end) => Some(Cow::Borrowed(end)),
+        None => prefix_successor(iter.prefix_key()?).map(Cow::Owned),
+    }
+}
+
 pub trait LevelDBIterator<'a> {
     type RevIter: LevelDBIterator<'a>;
 
@@ -114,23 +343,40 @@ pub trait LevelDBIterator<'a> {
     fn started(&mut self);
 
     fn reverse(self) -> Self::RevIter;
+    /// Whether the iterator runs from larger to smaller keys
+    ///
+    /// Defaults to false, reverse iterators must override it.
+    fn is_reverse(&self) -> bool {
+        false
+    }
 
-    fn from(self, key: &'a [u8]) -> Self;
-    fn to(self, key: &'a [u8]) -> Self;
-    fn prefix(self, key: &'a [u8]) -> Self;
+    fn from<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self;
+    fn to<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self;
+    fn prefix<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self;
 
     #[allow(clippy::wrong_self_convention)]
     fn from_key(&self) -> Option<&'a [u8]>;
     fn to_key(&self) -> Option<&'a [u8]>;
     fn prefix_key(&self) -> Option<&'a [u8]>;
+    /// The exclusive upper bound of the prefix, see `bytes::prefix_successor`.
+    ///
+    /// Iterators can store the bound when the prefix is set. Without it,
+    /// the default, it is derived from `prefix_key` where needed.
+    fn prefix_end_key(&self) -> Option<&[u8]> {
+        None
+    }
 
     fn valid(&self, reverse: bool) -> bool {
         if unsafe { leveldb_iter_valid(self.raw_iterator()) != 0 } {
+            // compare against leveldb's buffer instead of copying the key
+            let key = unsafe { raw_key(self.raw_iterator()) };
             if let Some(k) = self.prefix_key() {
-                // match the key with a byte prefix
-                if self.key()[..].starts_with(k) {
-                    return true;
-                }
+                // the prefix spans the range [prefix, prefix_successor)
+                let below_end = match self.prefix_end_key() {
+                    Some(end) => key < end,
+                    None => key.starts_with(k),
+                };
+                return key >= k && below_end;
             } else {
                 let from = if let Some(k) = self.from_key() {
                     let comparator: fn(&[u8], &[u8]) -> bool = if reverse {
@@ -138,7 +384,7 @@ pub trait LevelDBIterator<'a> {
                     } else {
                         |a: &[u8], b: &[u8]| -> bool { a >= b }
                     };
-                    comparator(&self.key()[..], k)
+                    comparator(key, k)
                 } else {
                     true
                 };
@@ -148,7 +394,7 @@ pub trait LevelDBIterator<'a> {
                     } else {
                         |a: &[u8], b: &[u8]| -> bool { a <= b }
                     };
-                    comparator(&self.key()[..], k)
+                    comparator(key, k)
                 } else {
                     true
                 };
@@ -165,19 +411,26 @@ pub trait LevelDBIterator<'a> {
 
     fn advance(&mut self, reverse: bool) -> bool {
         if !self.start() {
+            // leveldb must not be stepped past the end of the keyspace
+            if unsafe { leveldb_iter_valid(self.raw_iterator()) == 0 } {
+                return false;
+            }
             unsafe {
                 self.advance_raw();
             }
         } else {
             if let Some(k) = self.prefix_key() {
-                self.seek(k)
+                if reverse {
+                    // position on the last key before the end of the prefix range
+                    let end = prefix_end(self);
+                    unsafe { seek_to_last_within(self.raw_iterator(), None, end.as_deref()) }
+                } else {
+                    self.seek(k)
+                }
             } else if let Some(k) = self.from_key() {
-                self.seek(k);
-                if !self.valid(reverse) && reverse {
-                    // if from doesn't exist we seeked past it so go back
-                    unsafe {
-                        self.advance_raw();
-                    }
+                if reverse {
+                    // if from doesn't exist position on the key before it
+                    unsafe { seek_to_last_within(self.raw_iterator(), Some(k), None) }
                 } else {
                     self.seek(k)
                 }
@@ -191,16 +444,16 @@ pub trait LevelDBIterator<'a> {
     fn key(&self) -> Vec<u8> {
         unsafe {
             let length: size_t = 0;
-            let value = leveldb_iter_key(self.raw_iterator(), &length) as *const u8;
-            from_raw_parts(value, length as usize).to_vec()
+            let value = leveldb_iter_key(self.raw_iterator(), &length);
+            raw_slice(value, length).to_vec()
         }
     }
 
     fn value(&self) -> Vec<u8> {
         unsafe {
             let length: size_t = 0;
-            let value = leveldb_iter_value(self.raw_iterator(), &length) as *const u8;
-            from_raw_parts(value, length as usize).to_vec()
+            let value = leveldb_iter_value(self.raw_iterator(), &length);
+            raw_slice(value, length).to_vec()
         }
     }
 
@@ -214,13 +467,26 @@ pub trait LevelDBIterator<'a> {
         }
     }
 
+    /// Seek to the last key within the `to` or prefix bound
+    ///
+    /// Whether the key is also within the `from` bound can be checked with `valid`.
     fn seek_to_last(&self) {
-        if let Some(k) = self.to_key() {
-            self.seek(k);
+        let end = prefix_end(self);
+        unsafe { seek_to_last_within(self.raw_iterator(), self.to_key(), end.as_deref()) }
+    }
+
+    /// The key of the entry the iterator is positioned on
+    ///
+    /// Returns `None` before the first entry was yielded and once the iteration
+    /// ended, also when it stopped at its `to` or prefix bound. The position
+    /// can be restored with `seek`: after seeking to the saved key, the
+    /// iteration continues with the entry following it, provided the saved
+    /// key still exists.
+    fn tell(&self) -> Option<Vec<u8>> {
+        if self.start() || !self.valid(self.is_reverse()) {
+            None
         } else {
-            unsafe {
-                leveldb_iter_seek_to_last(self.raw_iterator());
-            }
+            Some(self.key())
         }
     }
 
@@ -233,6 +499,41 @@ pub trait LevelDBIterator<'a> {
             );
         }
     }
+
+    /// The status of the underlying leveldb iterator
+    ///
+    /// leveldb stops iterating when it hits an error, e.g. a checksum mismatch,
+    /// which looks like the regular end of the iteration. The status reports
+    /// that error, it is `Ok` as long as the iterator didn't run into one.
+    fn status(&self) -> Result<(), Error> {
+        let mut error = ptr::null_mut::<c_char>();
+        unsafe {
+            leveldb_iter_get_error(self.raw_iterator(), &mut error as *mut *mut c_char as _);
+            if error.is_null() {
+                Ok(())
+            } else {
+                Err(Error::new_from_char(error))
+            }
+        }
+    }
+
+    /// The error that made the iterator stop, if any, see `status`.
+    fn last_error(&self) -> Option<Error> {
+        self.status().err()
+    }
+
+    /// Yield the first item and every `stride`-th item after it.
+    ///
+    /// A `stride` of 0 is treated as 1, yielding every item.
+    fn sample(self, stride: usize) -> SampleIterator<Self>
+    where
+        Self: Sized,
+    {
+        SampleIterator {
+            inner: self,
+            stride: stride.max(1),
+        }
+    }
 }
 
 impl<'a> Iterator<'a> {
@@ -241,33 +542,230 @@ impl<'a> Iterator<'a> {
         options: &ReadOptions,
         snapshot: Option<&'a Snapshot>,
     ) -> Iterator<'a> {
-        unsafe {
-            let c_read_options = c_readoptions(options);
+        unsafe { Iterator::with_raw_snapshot(database, options, snapshot.map(|s| s.raw_ptr())) }
+    }
 
-            if let Some(snapshot) = snapshot {
-                leveldb_readoptions_set_snapshot(c_read_options, snapshot.raw_ptr());
-            }
+    /// # Safety
+    /// The snapshot must stay alive as long as the iterator.
+    pub(crate) unsafe fn with_raw_snapshot(
+        database: &'a Database,
+        options: &ReadOptions,
+        snapshot: Option<*mut leveldb_snapshot_t>,
+    ) -> Iterator<'a> {
+        let c_read_options = c_readoptions(&database.effective_read_options(options));
 
-            let ptr = leveldb_create_iterator(database.database.ptr, c_read_options);
+        if let Some(snapshot) = snapshot {
+            leveldb_readoptions_set_snapshot(c_read_options, snapshot);
+        }
 
-            leveldb_readoptions_destroy(c_read_options);
-            leveldb_iter_seek_to_first(ptr);
+        let ptr = leveldb_create_iterator(database.database.ptr, c_read_options);
 
-            Iterator {
-                start: true,
-                iter: RawIterator { ptr },
-                database: PhantomData,
-                from: None,
-                to: None,
-                prefix: None,
-            }
+        leveldb_readoptions_destroy(c_read_options);
+        leveldb_iter_seek_to_first(ptr);
+
+        Iterator {
+            start: true,
+            iter: RawIterator { ptr },
+            database: PhantomData,
+            from: None,
+            to: None,
+            prefix: None,
+            prefix_end: None,
         }
     }
 
     /// return the last element of the iterator
     pub fn last(&self) -> Option<(Vec<u8>, Vec<u8>)> {
         self.seek_to_last();
-        Some((self.key(), self.value()))
+        if self.valid(false) {
+            Some((self.key(), self.value()))
+        } else {
+            None
+        }
+    }
+
+    /// Whether the iterator points at an entry within its bounds
+    ///
+    /// Together with `seek` and `advance` this allows driving the iterator
+    /// manually. The first `advance` positions the iterator on the first
+    /// entry of its bounds.
+    pub fn is_valid(&self) -> bool {
+        self.valid(false)
+    }
+
+    /// Map the keys with `f`, keeping the values as they are
+    pub fn map_keys<T, F: Fn(&[u8]) -> T>(self, f: F) -> MapKeyIterator<'a, T, F> {
+        MapKeyIterator {
+            inner: self,
+            f,
+            marker: PhantomData,
+        }
+    }
+
+    /// Yield each entry together with its successor
+    ///
+    /// An iterator over `n` entries yields `n - 1` pairs, none if it has less
+    /// than two entries.
+    pub fn pairs(self) -> PairsIterator<'a> {
+        PairsIterator {
+            inner: self,
+            prev: None,
+        }
+    }
+
+    /// Yield entries while their keys start with `prefix`
+    ///
+    /// The scan ends at the first key not starting with `prefix`, which is left
+    /// to be returned by the next call to `next` on this iterator. This allows
+    /// scanning several adjacent prefixes one after the other with the same cursor.
+    pub fn take_while_prefix<'b>(&'b mut self, prefix: &'b [u8]) -> TakeWhilePrefix<'b, 'a> {
+        TakeWhilePrefix {
+            inner: self,
+            prefix,
+            done: false,
+        }
+    }
+
+    /// Yield only the entries whose keys match `pred`
+    pub fn filter_keys<F: Fn(&[u8]) -> bool>(self, pred: F) -> FilterKeysIterator<'a, F> {
+        FilterKeysIterator { inner: self, pred }
+    }
+
+    /// Yield the entries in batches of `size`
+    ///
+    /// Every batch holds `size` entries, except for the last one which holds
+    /// the remaining entries. Panics if `size` is 0.
+    pub fn chunks(self, size: usize) -> ChunksIterator<'a> {
+        assert!(size != 0, "chunk size must not be zero");
+
+        ChunksIterator { inner: self, size }
+    }
+
+    /// Yield the entries as `Result`s, ending with an `Err` if the iteration failed
+    pub fn results(self) -> TryIterator<'a> {
+        TryIterator {
+            inner: self,
+            done: false,
+        }
+    }
+
+    /// Yield the keys of the entries whose values contain `needle`
+    ///
+    /// Values are searched in leveldb's buffer, only the keys of matching
+    /// entries are copied.
+    pub fn values_contain(self, needle: &'a [u8]) -> ValuesContain<'a> {
+        ValuesContain {
+            inner: self,
+            needle,
+        }
+    }
+
+    /// Call `f` with every entry before yielding it, e.g. to log a scan
+    ///
+    /// Unlike `inspect`, `f` borrows the key and value from leveldb's buffer,
+    /// so inspecting copies nothing beyond the yielded entry.
+    pub fn inspect_entry<F: Fn(&[u8], &[u8])>(self, f: F) -> InspectIterator<'a, F> {
+        InspectIterator { inner: self, f }
+    }
+
+    /// Collect the remaining entries within the bounds into a `BTreeMap`
+    ///
+    /// The entries already arrive in key order, which lets the map build
+    /// its tree in bulk instead of inserting and rebalancing one by one.
+    pub fn into_btreemap(self) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        self.collect()
+    }
+}
+
+/// Iterate over the (key, value) pairs of two databases in key order
+///
+/// Keys present in both databases are yielded once, with the value of the
+/// database taking precedence, by default the first one (see
+/// `MergeIterator::precedence`).
+///
+/// Keys are compared with the comparator of the databases. Both must use
+/// comparators of the same name, otherwise an invalid argument error is
+/// returned.
+pub fn merge_iter<'a>(
+    first: &'a Database,
+    second: &'a Database,
+    options: &ReadOptions,
+) -> Result<MergeIterator<'a>, Error> {
+    let (first_name, second_name) = (comparator_name(first), comparator_name(second));
+    if first_name != second_name {
+        return Err(Error::new(format!(
+            "Invalid argument: merged databases use different comparators: {} and {}",
+            first_name, second_name
+        )));
+    }
+
+    Ok(MergeIterator {
+        database: first,
+        first: first.iter(options).peekable(),
+        second: second.iter(options).peekable(),
+        precedence: Precedence::First,
+    })
+}
+
+fn comparator_name(database: &Database) -> String {
+    match database.comparator {
+        Some(ref comparator) => comparator.name(),
+        None => "leveldb.BytewiseComparator".to_string(),
+    }
+}
+
+impl<'a> MergeIterator<'a> {
+    /// Set which database wins for keys present in both
+    pub fn precedence(mut self, precedence: Precedence) -> MergeIterator<'a> {
+        self.precedence = precedence;
+        self
+    }
+}
+
+impl ResumableScan {
+    /// A scan starting at the first key
+    pub fn new() -> ResumableScan {
+        ResumableScan::default()
+    }
+
+    /// The last key yielded, `None` if the scan didn't yield any entries yet
+    pub fn last_key(&self) -> Option<&[u8]> {
+        self.last_key.as_deref()
+    }
+
+    /// Continue the scan on `database` with the first key after `last_key`
+    pub fn resume<'s, 'a>(
+        &'s mut self,
+        database: &'a Database,
+        options: &ReadOptions,
+    ) -> ResumedIterator<'s, 'a> {
+        ResumedIterator {
+            inner: database.iter(options),
+            seek_pending: self.last_key.is_some(),
+            scan: self,
+        }
+    }
+}
+
+impl<'a> RevIterator<'a> {
+    /// Whether the iterator points at an entry within its bounds
+    ///
+    /// See `Iterator::is_valid`.
+    pub fn is_valid(&self) -> bool {
+        self.valid(true)
+    }
+
+    /// Yield only the first entry for each distinct key prefix of `prefix_len` bytes
+    ///
+    /// Keys sharing a prefix are adjacent, so for keys made of an id followed by
+    /// a version this yields the latest version of each id. Keys shorter than
+    /// `prefix_len` are their own prefix.
+    pub fn latest_per_prefix(self, prefix_len: usize) -> LatestPerPrefix<'a> {
+        LatestPerPrefix {
+            inner: self,
+            prefix_len,
+            last: None,
+        }
     }
 }
 
@@ -297,8 +795,14 @@ impl<'a> LevelDBIterator<'a> for Iterator<'a> {
     #[inline]
     fn reverse(self) -> Self::RevIter {
         if self.start {
+            // a reverse scan starts at its `from` bound or the end of its prefix
+            let upper = if self.prefix.is_some() {
+                None
+            } else {
+                self.from
+            };
             unsafe {
-                leveldb_iter_seek_to_last(self.iter.ptr);
+                seek_to_last_within(self.iter.ptr, upper, self.prefix_end.as_deref());
             }
         }
         RevIterator {
@@ -308,21 +812,28 @@ impl<'a> LevelDBIterator<'a> for Iterator<'a> {
             from: self.from,
             to: self.to,
             prefix: self.prefix,
+            prefix_end: self.prefix_end,
         }
     }
 
-    fn from(mut self, key: &'a [u8]) -> Self {
-        self.from = Some(key);
+    #[inline]
+    fn is_reverse(&self) -> bool {
+        false
+    }
+
+    fn from<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.from = Some(key.as_ref());
         self
     }
 
-    fn to(mut self, key: &'a [u8]) -> Self {
-        self.to = Some(key);
+    fn to<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.to = Some(key.as_ref());
         self
     }
 
-    fn prefix(mut self, key: &'a [u8]) -> Self {
-        self.prefix = Some(key);
+    fn prefix<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.prefix = Some(key.as_ref());
+        self.prefix_end = prefix_successor(key.as_ref());
         self
     }
 
@@ -337,6 +848,10 @@ impl<'a> LevelDBIterator<'a> for Iterator<'a> {
     fn prefix_key(&self) -> Option<&'a [u8]> {
         self.prefix
     }
+
+    fn prefix_end_key(&self) -> Option<&[u8]> {
+        self.prefix_end.as_deref()
+    }
 }
 
 impl<'a> LevelDBIterator<'a> for RevIterator<'a> {
@@ -365,8 +880,10 @@ impl<'a> LevelDBIterator<'a> for RevIterator<'a> {
     #[inline]
     fn reverse(self) -> Self::RevIter {
         if self.start {
-            unsafe {
-                leveldb_iter_seek_to_first(self.iter.ptr);
+            // a forward scan starts at its prefix or `from` bound
+            match self.prefix.or(self.from) {
+                Some(k) => self.seek(k),
+                None => self.seek_to_first(),
             }
         }
         Iterator {
@@ -376,21 +893,28 @@ impl<'a> LevelDBIterator<'a> for RevIterator<'a> {
             from: self.from,
             to: self.to,
             prefix: self.prefix,
+            prefix_end: self.prefix_end,
         }
     }
 
-    fn from(mut self, key: &'a [u8]) -> Self {
-        self.from = Some(key);
+    #[inline]
+    fn is_reverse(&self) -> bool {
+        true
+    }
+
+    fn from<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.from = Some(key.as_ref());
         self
     }
 
-    fn to(mut self, key: &'a [u8]) -> Self {
-        self.to = Some(key);
+    fn to<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.to = Some(key.as_ref());
         self
     }
 
-    fn prefix(mut self, key: &'a [u8]) -> Self {
-        self.prefix = Some(key);
+    fn prefix<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+        self.prefix = Some(key.as_ref());
+        self.prefix_end = prefix_successor(key.as_ref());
         self
     }
 
@@ -405,6 +929,10 @@ impl<'a> LevelDBIterator<'a> for RevIterator<'a> {
     fn prefix_key(&self) -> Option<&'a [u8]> {
         self.prefix
     }
+
+    fn prefix_end_key(&self) -> Option<&[u8]> {
+        self.prefix_end.as_deref()
+    }
 }
 
 impl<'a> KeyIterator<'a> {
@@ -421,7 +949,11 @@ impl<'a> KeyIterator<'a> {
     /// return the last element of the iterator
     pub fn last(self) -> Option<Vec<u8>> {
         self.seek_to_last();
-        Some(self.key())
+        if self.valid(false) {
+            Some(self.key())
+        } else {
+            None
+        }
     }
 }
 
@@ -439,7 +971,11 @@ impl<'a> ValueIterator<'a> {
     /// return the last element of the iterator
     pub fn last(self) -> Option<Vec<u8>> {
         self.seek_to_last();
-        Some(self.value())
+        if self.valid(false) {
+            Some(self.value())
+        } else {
+            None
+        }
     }
 }
 
@@ -475,18 +1011,23 @@ macro_rules! impl_leveldb_iterator {
                 }
             }
 
-            fn from(mut self, key: &'a [u8]) -> Self {
-                self.inner.from = Some(key);
+            #[inline]
+            fn is_reverse(&self) -> bool {
+                self.inner.is_reverse()
+            }
+
+            fn from<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+                self.inner.from = Some(key.as_ref());
                 self
             }
 
-            fn to(mut self, key: &'a [u8]) -> Self {
-                self.inner.to = Some(key);
+            fn to<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+                self.inner.to = Some(key.as_ref());
                 self
             }
 
-            fn prefix(mut self, key: &'a [u8]) -> Self {
-                self.inner.prefix = Some(key);
+            fn prefix<K: AsRef<[u8]> + ?Sized>(mut self, key: &'a K) -> Self {
+                self.inner = self.inner.prefix(key);
                 self
             }
 
@@ -501,6 +1042,10 @@ macro_rules! impl_leveldb_iterator {
             fn prefix_key(&self) -> Option<&'a [u8]> {
                 self.inner.prefix
             }
+
+            fn prefix_end_key(&self) -> Option<&[u8]> {
+                self.inner.prefix_end.as_deref()
+            }
         }
     };
 }
@@ -522,6 +1067,47 @@ macro_rules! impl_iterator {
                     None
                 }
             }
+
+            // step over the skipped entries with `advance`, without building
+            // items for them
+            fn nth(&mut self, n: usize) -> Option<Self::Item> {
+                for _ in 0..n {
+                    if !self.advance($Rev) {
+                        return None;
+                    }
+                }
+                self.next()
+            }
+
+            // counted entries are only stepped over, not copied
+            fn count(mut self) -> usize {
+                let mut count = 0;
+                while self.advance($Rev) {
+                    count += 1;
+                }
+                count
+            }
+        }
+
+        impl<'a> $T {
+            /// The items following the current position
+            ///
+            /// Entries consumed before, by `next` or by driving the iterator with
+            /// `advance`, are not yielded again. The iterator can be used further
+            /// once the returned iterator is dropped.
+            pub fn remaining(&mut self) -> Remaining<'_, Self> {
+                Remaining { inner: self }
+            }
+
+            /// Advance the iterator, reporting errors instead of ending the iteration
+            ///
+            /// Returns `Ok(None)` at the regular end of the iteration.
+            pub fn try_next(&mut self) -> Result<Option<$Item>, Error> {
+                match iter::Iterator::next(self) {
+                    Some(item) => Ok(Some(item)),
+                    None => self.status().map(|_| None),
+                }
+            }
         }
     };
 }
@@ -531,4 +1117,319 @@ impl_iterator!(RevIterator<'a>, (Vec<u8>, Vec<u8>), entry, true);
 impl_iterator!(KeyIterator<'a>, Vec<u8>, key, false);
 impl_iterator!(RevKeyIterator<'a>, Vec<u8>, key, true);
 impl_iterator!(ValueIterator<'a>, Vec<u8>, value, false);
-impl_iterator!(RevValueIterator<'a>, Vec<u8>, key, true);
\ No newline at end of file
+impl_iterator!(RevValueIterator<'a>, Vec<u8>, key, true);
+
+impl<I: iter::Iterator<Item = Vec<u8>>, T: FromU8> iter::Iterator for DecodedKeys<I, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|key| T::from_u8(&key))
+    }
+}
+
+impl<I: iter::Iterator<Item = (Vec<u8>, Vec<u8>)>, T: FromU8> iter::Iterator
+    for DecodedEntries<I, T>
+{
+    type Item = (T, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(key, value)| (T::from_u8(&key), value))
+    }
+}
+
+impl<'a, T, F: Fn(&[u8]) -> T> iter::Iterator for MapKeyIterator<'a, T, F> {
+    type Item = (T, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.inner.advance(false) {
+            let key = unsafe { raw_key(self.inner.raw_iterator()) };
+            Some(((self.f)(key), self.inner.value()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> iter::Iterator for PairsIterator<'a> {
+    type Item = ((Vec<u8>, Vec<u8>), (Vec<u8>, Vec<u8>));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prev = match self.prev.take() {
+            Some(prev) => prev,
+            None => self.inner.next()?,
+        };
+        let next = self.inner.next()?;
+        self.prev = Some(next.clone());
+        Some((prev, next))
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+impl<'s, 'a> iter::Iterator for ResumedIterator<'s, 'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = if self.seek_pending {
+            self.seek_pending = false;
+            // the smallest key following the last one
+            let mut after = self.scan.last_key.clone().unwrap_or_default();
+            after.push(0);
+            self.inner.seek(&after);
+            self.inner.started();
+            if !self.inner.is_valid() {
+                return None;
+            }
+            self.inner.entry()
+        } else {
+            self.inner.next()?
+        };
+
+        self.scan.last_key = Some(entry.0.clone());
+        Some(entry)
+    }
+}
+
+impl<'r, I: iter::Iterator> iter::Iterator for Remaining<'r, I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.inner.nth(n)
+    }
+}
+
+impl<'a, F: Fn(&[u8], &[u8])> iter::Iterator for InspectIterator<'a, F> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.inner.advance(false) {
+            return None;
+        }
+        let iter = self.inner.raw_iterator();
+        let (key, value) = unsafe { (raw_key(iter), raw_value(iter)) };
+        (self.f)(key, value);
+
+        Some((key.to_vec(), value.to_vec()))
+    }
+}
+
+impl<'a> iter::Iterator for MergeIterator<'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.first.peek(), self.second.peek()) {
+            (Some((a, _)), Some((b, _))) => match self.database.comparator {
+                Some(ref comparator) => comparator.compare(a, b),
+                None => a.cmp(b),
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+
+        match order {
+            Ordering::Less => self.first.next(),
+            Ordering::Greater => self.second.next(),
+            Ordering::Equal => {
+                let first = self.first.next();
+                let second = self.second.next();
+                match self.precedence {
+                    Precedence::First => first,
+                    Precedence::Second => second,
+                }
+            }
+        }
+    }
+}
+
+impl<'a> iter::Iterator for ValuesContain<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.inner.advance(false) {
+            let value = unsafe { raw_value(self.inner.raw_iterator()) };
+            if contains(value, self.needle) {
+                return Some(self.inner.key());
+            }
+        }
+        None
+    }
+}
+
+impl<'a> iter::Iterator for TryIterator<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.inner.try_next() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a> iter::Iterator for LatestPerPrefix<'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.inner.advance(true) {
+            let key = unsafe { raw_key(self.inner.raw_iterator()) };
+            let prefix = &key[..key.len().min(self.prefix_len)];
+            if self.last.as_deref() != Some(prefix) {
+                self.last = Some(prefix.to_vec());
+                return Some(self.inner.entry());
+            }
+        }
+        None
+    }
+}
+
+impl<'b, 'a> iter::Iterator for TakeWhilePrefix<'b, 'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let positioned = !self.inner.start;
+        if !self.inner.advance(false) {
+            self.done = true;
+            return None;
+        }
+
+        if unsafe { raw_key(self.inner.raw_iterator()) }.starts_with(self.prefix) {
+            return Some(self.inner.entry());
+        }
+
+        // step back, so that the inner iterator yields the key next
+        self.done = true;
+        if positioned {
+            unsafe { leveldb_iter_prev(self.inner.raw_iterator()) };
+        } else {
+            self.inner.start = true;
+        }
+        None
+    }
+}
+
+impl<'a, F: Fn(&[u8]) -> bool> iter::Iterator for FilterKeysIterator<'a, F> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.inner.advance(false) {
+            if (self.pred)(unsafe { raw_key(self.inner.raw_iterator()) }) {
+                return Some(self.inner.entry());
+            }
+        }
+        None
+    }
+}
+
+impl<'a> iter::Iterator for ChunksIterator<'a> {
+    type Item = Vec<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::with_capacity(self.size);
+        chunk.extend(self.inner.by_ref().take(self.size));
+
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(chunk)
+        }
+    }
+}
+
+macro_rules! impl_decoded {
+    ($T:ty, $Adapter:ident, $method:ident) => {
+        impl<'a> $T {
+            /// Decode the keys using `FromU8`, panicking on malformed keys.
+            pub fn $method<K: FromU8>(self) -> $Adapter<Self, K> {
+                $Adapter {
+                    inner: self,
+                    marker: PhantomData,
+                }
+            }
+        }
+    };
+}
+
+impl_decoded!(KeyIterator<'a>, DecodedKeys, keys_decoded);
+impl_decoded!(RevKeyIterator<'a>, DecodedKeys, keys_decoded);
+impl_decoded!(Iterator<'a>, DecodedEntries, entries_decoded);
+impl_decoded!(RevIterator<'a>, DecodedEntries, entries_decoded);
+
+macro_rules! impl_strip_prefix {
+    ($T:ty, $Item:ty, $strip:ident) => {
+        impl<'a> $T {
+            /// Iterate over the keys starting with `prefix`, removing the prefix
+            /// from the yielded keys.
+            pub fn strip_prefix(self, prefix: &'a [u8]) -> StripPrefixIterator<Self> {
+                StripPrefixIterator {
+                    inner: self.prefix(prefix),
+                    prefix_len: prefix.len(),
+                }
+            }
+        }
+
+        impl<'a> iter::Iterator for StripPrefixIterator<$T> {
+            type Item = $Item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                let prefix_len = self.prefix_len;
+                self.inner.next().map(|item| $strip(item, prefix_len))
+            }
+        }
+    };
+}
+
+fn strip_entry((key, value): (Vec<u8>, Vec<u8>), prefix_len: usize) -> (Vec<u8>, Vec<u8>) {
+    (key[prefix_len..].to_vec(), value)
+}
+
+fn strip_key(key: Vec<u8>, prefix_len: usize) -> Vec<u8> {
+    key[prefix_len..].to_vec()
+}
+
+impl_strip_prefix!(Iterator<'a>, (Vec<u8>, Vec<u8>), strip_entry);
+impl_strip_prefix!(KeyIterator<'a>, Vec<u8>, strip_key);
+
+impl<'a, I: LevelDBIterator<'a> + iter::Iterator> iter::Iterator for SampleIterator<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.inner.start() {
+            for _ in 1..self.stride {
+                if unsafe { leveldb_iter_valid(self.inner.raw_iterator()) == 0 } {
+                    return None;
+                }
+                unsafe {
+                    self.inner.advance_raw();
+                }
+            }
+            if unsafe { leveldb_iter_valid(self.inner.raw_iterator()) == 0 } {
+                return None;
+            }
+        }
+
+        self.inner.next()
+    }
+}
@@ -1,4 +1,7 @@
 use super::error::Error;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::Arc;
 
 pub type KeyFn<'a, T> = &'a dyn Fn(&[u8]) -> Result<T, Error>;
 
@@ -47,6 +50,48 @@ impl IntoLevelDBKey for Vec<u8> {
     }
 }
 
+impl IntoLevelDBKey for [u8] {
+    fn as_u8_slice_for_write(&self, f: KeyFn<()>) -> Result<(), Error> {
+        f(self)
+    }
+
+    fn as_u8_slice_for_get(&self, f: KeyFn<Option<Vec<u8>>>) -> Result<Option<Vec<u8>>, Error> {
+        f(self)
+    }
+}
+
+impl IntoLevelDBKey for str {
+    fn as_u8_slice_for_write(&self, f: KeyFn<()>) -> Result<(), Error> {
+        f(self.as_bytes())
+    }
+
+    fn as_u8_slice_for_get(&self, f: KeyFn<Option<Vec<u8>>>) -> Result<Option<Vec<u8>>, Error> {
+        f(self.as_bytes())
+    }
+}
+
+// Smart pointers to keys, e.g. `Rc<[u8]>` or `Arc<Vec<u8>>`, are keys themselves.
+macro_rules! impl_into_level_db_key_for_pointer {
+    ($P: ident) => {
+        impl<K: IntoLevelDBKey + ?Sized> IntoLevelDBKey for $P<K> {
+            fn as_u8_slice_for_write(&self, f: KeyFn<()>) -> Result<(), Error> {
+                (**self).as_u8_slice_for_write(f)
+            }
+
+            fn as_u8_slice_for_get(
+                &self,
+                f: KeyFn<Option<Vec<u8>>>,
+            ) -> Result<Option<Vec<u8>>, Error> {
+                (**self).as_u8_slice_for_get(f)
+            }
+        }
+    };
+}
+
+impl_into_level_db_key_for_pointer!(Box);
+impl_into_level_db_key_for_pointer!(Rc);
+impl_into_level_db_key_for_pointer!(Arc);
+
 macro_rules! impl_into_level_db_key_for_integer {
     ($T: ty) => {
         impl IntoLevelDBKey for $T {
@@ -74,3 +119,80 @@ impl_into_level_db_key_for_integer!(u16);
 impl_into_level_db_key_for_integer!(u32);
 impl_into_level_db_key_for_integer!(u64);
 impl_into_level_db_key_for_integer!(u128);
+
+/// A key space stored alongside others in the same database
+///
+/// The tag is written in front of every `TypedKey` of that space, so it
+/// must be unique among the key spaces sharing a database.
+pub trait KeyTag {
+    const TAG: &'static [u8];
+}
+
+/// A key that belongs to the key space `T`
+///
+/// The encoded key is the length of `T::TAG` as a single byte, the tag
+/// itself and then the raw key, so keys of different spaces never collide
+/// even if one tag is a prefix of another. Keys of different spaces are
+/// different types and can't be mixed up:
+///
+/// ```compile_fail
+/// use leveldb::key::{KeyTag, TypedKey};
+///
+/// struct UserId;
+/// impl KeyTag for UserId {
+///     const TAG: &'static [u8] = b"user";
+/// }
+///
+/// struct OrderId;
+/// impl KeyTag for OrderId {
+///     const TAG: &'static [u8] = b"order";
+/// }
+///
+/// let key: TypedKey<OrderId> = TypedKey::<UserId>::new(b"42");
+/// ```
+pub struct TypedKey<T> {
+    bytes: Vec<u8>,
+    tag: PhantomData<T>,
+}
+
+impl<T: KeyTag> TypedKey<T> {
+    /// Encode `key` in the key space `T`
+    ///
+    /// Panics if `T::TAG` is longer than 255 bytes.
+    pub fn new(key: &[u8]) -> TypedKey<T> {
+        assert!(
+            T::TAG.len() <= u8::MAX as usize,
+            "key tag is longer than 255 bytes"
+        );
+
+        let mut bytes = Vec::with_capacity(1 + T::TAG.len() + key.len());
+        bytes.push(T::TAG.len() as u8);
+        bytes.extend_from_slice(T::TAG);
+        bytes.extend_from_slice(key);
+
+        TypedKey {
+            bytes,
+            tag: PhantomData,
+        }
+    }
+
+    /// The raw key, without the tag
+    pub fn key(&self) -> &[u8] {
+        &self.bytes[1 + T::TAG.len()..]
+    }
+
+    /// The encoded key as stored in the database
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<T> IntoLevelDBKey for TypedKey<T> {
+    fn as_u8_slice_for_write(&self, f: KeyFn<()>) -> Result<(), Error> {
+        f(&self.bytes)
+    }
+
+    fn as_u8_slice_for_get(&self, f: KeyFn<Option<Vec<u8>>>) -> Result<Option<Vec<u8>>, Error> {
+        f(&self.bytes)
+    }
+}
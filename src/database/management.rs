@@ -1,9 +1,15 @@
+use super::batch::{Batch, WriteBatch};
+use super::db::{manifest_records, Database, DatabaseReader, VersionEdit};
 use super::error::Error;
-use super::options::{c_options, Options};
+use super::iterator::{Iterable, Iterator, KeyIterator, LevelDBIterator, ValueIterator};
+use super::key::IntoLevelDBKey;
+use super::options::{c_options, Options, ReadOptions, WriteOptions};
 use libc::c_char;
+use std::collections::BTreeSet;
 use std::ffi::CString;
 use std::path::Path;
-use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io, iter, ptr};
 
 use leveldb_sys::{leveldb_destroy_db, leveldb_repair_db};
 
@@ -45,4 +51,487 @@ pub fn repair(name: &Path, options: &Options) -> Result<(), Error> {
             Err(Error::new_from_char(error))
         }
     }
-}
\ No newline at end of file
+}
+
+/// The kind of a file in a database directory, by leveldb's naming convention
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileType {
+    /// A table file, `NNNNNN.ldb` or `NNNNNN.sst`
+    Sstable,
+    /// A write-ahead log, `NNNNNN.log`
+    Log,
+    /// A manifest describing the table files, `MANIFEST-NNNNNN`
+    Manifest,
+    /// The file naming the current manifest
+    Current,
+    /// The lock file held while the database is open
+    Lock,
+    /// Any other file, like the info log `LOG`
+    Other,
+}
+
+/// A file in a database directory, see `list_files`
+#[derive(Clone, Debug)]
+pub struct FileInfo {
+    /// The file name within the database directory
+    pub name: String,
+    /// The kind of the file
+    pub file_type: FileType,
+    /// The size of the file in bytes
+    pub size: u64,
+}
+
+fn file_type(name: &str) -> FileType {
+    let numbered = |stem: &str| !stem.is_empty() && stem.bytes().all(|b| b.is_ascii_digit());
+
+    match name {
+        "CURRENT" => FileType::Current,
+        "LOCK" => FileType::Lock,
+        _ if name.starts_with("MANIFEST-") && numbered(&name["MANIFEST-".len()..]) => {
+            FileType::Manifest
+        }
+        _ => match name.split_once('.') {
+            Some((stem, "ldb")) | Some((stem, "sst")) if numbered(stem) => FileType::Sstable,
+            Some((stem, "log")) if numbered(stem) => FileType::Log,
+            _ => FileType::Other,
+        },
+    }
+}
+
+/// List the files of the database at `path`, sorted by name
+///
+/// The database doesn't need to be open. If it is, compactions may add and
+/// remove files while they are listed.
+pub fn list_files<P: AsRef<Path>>(path: P) -> Result<Vec<FileInfo>, Error> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        files.push(FileInfo {
+            file_type: file_type(&name),
+            name,
+            size: metadata.len(),
+        });
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(files)
+}
+
+// a compaction may remove table files while a checkpoint is taken
+const CHECKPOINT_ATTEMPTS: usize = 5;
+
+/// Structs implementing the Checkpoint trait can create
+/// point-in-time copies of their files.
+pub trait Checkpoint {
+    /// Create a checkpoint of the database in `dest`
+    ///
+    /// Table files are immutable and hard-linked into `dest`, falling back to a
+    /// copy if linking fails, e.g. across filesystems. The manifest and the write
+    /// ahead logs are copied, `CURRENT` is written last, so an interrupted
+    /// checkpoint can't be opened. The checkpoint can be opened as an independent
+    /// database containing the writes completed before the call.
+    ///
+    /// No snapshot is taken: a snapshot only pins the sequence number reads
+    /// see, it doesn't keep compactions from removing files. The file set is
+    /// pinned by the copied manifest instead, which names the tables and the
+    /// log of one consistent version. If a compaction removes one of them
+    /// before it is linked, the checkpoint is retried with the new manifest.
+    fn checkpoint<P: AsRef<Path>>(&self, dest: P) -> Result<(), Error>;
+}
+
+impl Checkpoint for Database {
+    fn checkpoint<P: AsRef<Path>>(&self, dest: P) -> Result<(), Error> {
+        let dest = dest.as_ref();
+        fs::create_dir_all(dest)?;
+
+        let mut attempt = 1;
+        loop {
+            let res = checkpoint_files(&self.path, dest);
+            let not_found = matches!(res, Err(ref e) if e.kind() == io::ErrorKind::NotFound);
+
+            if not_found && attempt < CHECKPOINT_ATTEMPTS {
+                attempt += 1;
+            } else {
+                return res.map_err(Error::from);
+            }
+        }
+    }
+}
+
+/// The files referenced by a manifest
+struct LiveFiles {
+    tables: BTreeSet<u64>,
+    log_number: u64,
+    // logs before this one were written to tables
+    min_log_number: u64,
+}
+
+impl LiveFiles {
+    fn read(manifest: &Path) -> io::Result<LiveFiles> {
+        let corrupt = |e: Error| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+        let mut live = LiveFiles {
+            tables: BTreeSet::new(),
+            log_number: 0,
+            min_log_number: 0,
+        };
+        let mut prev_log_number = 0;
+        for record in manifest_records(&fs::read(manifest)?).map_err(corrupt)? {
+            let edit = VersionEdit::decode(&record).map_err(corrupt)?;
+            for number in edit.deleted_files {
+                live.tables.remove(&number);
+            }
+            live.tables.extend(edit.new_files);
+            live.log_number = edit.log_number.unwrap_or(live.log_number);
+            prev_log_number = edit.prev_log_number.unwrap_or(prev_log_number);
+        }
+        live.min_log_number = match prev_log_number {
+            0 => live.log_number,
+            prev => prev.min(live.log_number),
+        };
+
+        Ok(live)
+    }
+}
+
+fn checkpoint_files(src: &Path, dest: &Path) -> io::Result<()> {
+    let current = fs::read_to_string(src.join("CURRENT"))?;
+    let manifest = current.trim_end();
+    // the copied manifest decides which files the checkpoint needs, if one of
+    // them is removed by a concurrent compaction before it is linked we retry
+    fs::copy(src.join(manifest), dest.join(manifest))?;
+    let live = LiveFiles::read(&dest.join(manifest))?;
+
+    for number in live.tables {
+        let mut name = format!("{:06}.ldb", number);
+        if !src.join(&name).exists() {
+            // tables written by older leveldb versions
+            name = format!("{:06}.sst", number);
+        }
+        let target = dest.join(&name);
+        if target.exists() {
+            fs::remove_file(&target)?;
+        }
+        if fs::hard_link(src.join(&name), &target).is_err() {
+            fs::copy(src.join(&name), &target)?;
+        }
+    }
+
+    // the log named in the manifest and the ones started after it
+    let mut copied_log = live.log_number == 0;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".log"))
+            .and_then(|number| number.parse::<u64>().ok());
+        match number {
+            Some(number) if number >= live.min_log_number => {
+                fs::copy(entry.path(), dest.join(&name))?;
+                copied_log |= number == live.log_number;
+            }
+            _ => {}
+        }
+    }
+    if !copied_log {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:06}.log was removed", live.log_number),
+        ));
+    }
+
+    fs::write(dest.join("CURRENT"), current)
+}
+
+/// The prefix of the keys metadata is stored under
+pub const META_PREFIX: &[u8] = b"\x00meta\x00";
+
+/// Structs implementing the Metadata trait can store metadata, like a
+/// schema version, next to the user data.
+///
+/// Metadata is stored under keys starting with `META_PREFIX`, user keys must
+/// not start with it. Such keys are still visible to iterators over the whole
+/// keyspace.
+pub trait Metadata {
+    /// Store `value` as the metadata `name`, writing synchronously.
+    fn set_meta(&self, name: &str, value: &[u8]) -> Result<(), Error>;
+    /// Read the metadata `name`.
+    fn get_meta(&self, name: &str) -> Result<Option<Vec<u8>>, Error>;
+}
+
+fn meta_key(name: &str) -> Vec<u8> {
+    let mut key = META_PREFIX.to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+impl Metadata for Database {
+    fn set_meta(&self, name: &str, value: &[u8]) -> Result<(), Error> {
+        let mut options = WriteOptions::new();
+        options.sync = true;
+        self.put_u8(&options, &meta_key(name), value)
+    }
+
+    fn get_meta(&self, name: &str) -> Result<Option<Vec<u8>>, Error> {
+        self.get_u8(&ReadOptions::new(), &meta_key(name))
+    }
+}
+
+/// A namespace inside a database
+///
+/// All keys are transparently prefixed with the namespace prefix on writes and
+/// reads, and the prefix is stripped again during iteration. Iteration is
+/// confined to the keys of the namespace.
+pub struct Namespace<'a> {
+    database: &'a Database,
+    prefix: Vec<u8>,
+}
+
+impl<'a> Namespace<'a> {
+    /// The prefix prepended to all keys of this namespace.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    fn scoped_key(&self, key: &[u8]) -> Vec<u8> {
+        let mut scoped = Vec::with_capacity(self.prefix.len() + key.len());
+        scoped.extend_from_slice(&self.prefix);
+        scoped.extend_from_slice(key);
+        scoped
+    }
+
+    pub fn put(
+        &self,
+        options: &WriteOptions,
+        key: &dyn IntoLevelDBKey,
+        value: &[u8],
+    ) -> Result<(), Error> {
+        key.as_u8_slice_for_write(&|k| self.put_u8(options, k, value))
+    }
+
+    pub fn put_u8(&self, options: &WriteOptions, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.database.put_u8(options, &self.scoped_key(key), value)
+    }
+
+    pub fn get(
+        &self,
+        options: &ReadOptions,
+        key: &dyn IntoLevelDBKey,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        key.as_u8_slice_for_get(&|k| self.get_u8(options, k))
+    }
+
+    pub fn get_u8(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.database.get_u8(options, &self.scoped_key(key))
+    }
+
+    pub fn delete(&self, options: &WriteOptions, key: &dyn IntoLevelDBKey) -> Result<(), Error> {
+        key.as_u8_slice_for_write(&|k| self.delete_u8(options, k))
+    }
+
+    pub fn delete_u8(&self, options: &WriteOptions, key: &[u8]) -> Result<(), Error> {
+        self.database.delete_u8(options, &self.scoped_key(key))
+    }
+
+    /// Return an iterator over the (key, value) pairs of the namespace, with the prefix stripped
+    pub fn iter(&self, options: &ReadOptions) -> NamespaceIterator<'_> {
+        NamespaceIterator {
+            inner: self.database.iter(options).prefix(&self.prefix),
+            prefix_len: self.prefix.len(),
+        }
+    }
+
+    /// Return an iterator over the keys of the namespace, with the prefix stripped
+    pub fn keys_iter(&self, options: &ReadOptions) -> NamespaceKeyIterator<'_> {
+        NamespaceKeyIterator {
+            inner: self.database.keys_iter(options).prefix(&self.prefix),
+            prefix_len: self.prefix.len(),
+        }
+    }
+
+    /// Return an iterator over the values of the namespace
+    pub fn value_iter(&self, options: &ReadOptions) -> ValueIterator<'_> {
+        self.database.value_iter(options).prefix(&self.prefix)
+    }
+}
+
+impl DatabaseReader for Namespace<'_> {
+    fn get(
+        &self,
+        options: &ReadOptions,
+        key: &dyn IntoLevelDBKey,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.get(options, key)
+    }
+
+    fn get_u8(
+        &self,
+        options: &ReadOptions,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.get_u8(options, key)
+    }
+}
+
+/// An iterator over the (key, value) pairs of a namespace.
+pub struct NamespaceIterator<'a> {
+    inner: Iterator<'a>,
+    prefix_len: usize,
+}
+
+/// An iterator over the keys of a namespace.
+pub struct NamespaceKeyIterator<'a> {
+    inner: KeyIterator<'a>,
+    prefix_len: usize,
+}
+
+impl<'a> iter::Iterator for NamespaceIterator<'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|(key, value)| (key[self.prefix_len..].to_vec(), value))
+    }
+}
+
+impl<'a> iter::Iterator for NamespaceKeyIterator<'a> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|key| key[self.prefix_len..].to_vec())
+    }
+}
+
+/// Structs implementing the Namespaces trait can be
+/// split into prefixed namespaces.
+pub trait Namespaces {
+    /// Creates a namespace scoping all keys under `prefix`.
+    fn namespace(&self, prefix: &[u8]) -> Namespace<'_>;
+}
+
+impl Namespaces for Database {
+    fn namespace(&self, prefix: &[u8]) -> Namespace<'_> {
+        Namespace {
+            database: self,
+            prefix: prefix.to_vec(),
+        }
+    }
+}
+
+// the length of the expiry appended to every value of a `TtlDatabase`
+const EXPIRY_LEN: usize = 8;
+
+/// A database whose entries expire
+///
+/// Every value is stored followed by its expiry, in milliseconds since the
+/// Unix epoch as a big-endian `u64`, so each entry takes 8 bytes more than
+/// its value. Reads strip the expiry again and treat expired entries as
+/// missing. Expired entries are removed when read or by `purge_expired`,
+/// leveldb itself knows nothing about them.
+///
+/// All entries of the database must be written through `put_ttl`. Its
+/// writes are serialized with the removal of expired entries, so an entry
+/// written again after it was found expired is never removed.
+pub struct TtlDatabase<'a> {
+    database: &'a Database,
+}
+
+impl<'a> TtlDatabase<'a> {
+    /// Wrap `database`, whose entries were all written with `put_ttl`
+    pub fn new(database: &'a Database) -> TtlDatabase<'a> {
+        TtlDatabase { database }
+    }
+
+    /// Store `value` for `key`, expiring once `ttl` passed
+    pub fn put_ttl(
+        &self,
+        options: &WriteOptions,
+        key: &[u8],
+        value: &[u8],
+        ttl: Duration,
+    ) -> Result<(), Error> {
+        let expiry = millis_since_epoch(SystemTime::now() + ttl);
+
+        let mut stored = Vec::with_capacity(value.len() + EXPIRY_LEN);
+        stored.extend_from_slice(value);
+        stored.extend_from_slice(&expiry.to_be_bytes());
+
+        let _removal = self.database.commit_lock.lock().unwrap();
+        self.database.put_u8(options, key, &stored)
+    }
+
+    /// Read the value of `key`, `None` if it is missing or expired
+    ///
+    /// An expired entry is deleted, unless it was written again since it
+    /// was read.
+    pub fn get(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let mut stored = match self.database.get_u8(options, key)? {
+            Some(stored) => stored,
+            None => return Ok(None),
+        };
+        let expiry = split_expiry(&stored).ok_or_else(|| {
+            Error::new("Corruption: value without expiry".to_string()).for_key(key)
+        })?;
+
+        if expiry <= millis_since_epoch(SystemTime::now()) {
+            self.delete_unchanged(key, &stored)?;
+            return Ok(None);
+        }
+        stored.truncate(stored.len() - EXPIRY_LEN);
+        Ok(Some(stored))
+    }
+
+    /// Delete the expired entries, returning how many were deleted
+    ///
+    /// The whole database is scanned, the deletes are written as one batch.
+    /// Entries too short to hold an expiry are left alone. `put_ttl` waits
+    /// until the batch was written.
+    pub fn purge_expired(&self, options: &WriteOptions) -> Result<u64, Error> {
+        let _removal = self.database.commit_lock.lock().unwrap();
+        let now = millis_since_epoch(SystemTime::now());
+        let batch = WriteBatch::new();
+        let mut purged = 0;
+
+        let mut iter = self.database.iter(&ReadOptions::new());
+        while let Some((key, stored)) = iter.try_next()? {
+            if matches!(split_expiry(&stored), Some(expiry) if expiry <= now) {
+                batch.delete_u8(&key);
+                purged += 1;
+            }
+        }
+        self.database.write(options, &batch)?;
+
+        Ok(purged)
+    }
+
+    // Delete `key` if it still holds `stored`, i.e. wasn't written since it was read
+    fn delete_unchanged(&self, key: &[u8], stored: &[u8]) -> Result<(), Error> {
+        let _removal = self.database.commit_lock.lock().unwrap();
+        if self.database.get_u8(&ReadOptions::new(), key)?.as_deref() == Some(stored) {
+            self.database.delete_u8(&WriteOptions::new(), key)?;
+        }
+        Ok(())
+    }
+}
+
+// the expiry at the end of a stored value
+fn split_expiry(stored: &[u8]) -> Option<u64> {
+    let at = stored.len().checked_sub(EXPIRY_LEN)?;
+    let mut expiry = [0; EXPIRY_LEN];
+    expiry.copy_from_slice(&stored[at..]);
+    Some(u64::from_be_bytes(expiry))
+}
+
+fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or(0)
+}
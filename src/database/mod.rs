@@ -5,11 +5,13 @@ pub mod compaction;
 pub mod comparator;
 pub mod db;
 pub mod error;
+pub mod filter;
 pub mod iterator;
 pub mod key;
 pub mod management;
 pub mod options;
 pub mod snapshots;
+pub mod transaction;
 pub mod util;
 
 pub use db::{Database, DatabaseReader};
@@ -1,8 +1,24 @@
 use leveldb_sys::*;
 
 use libc::size_t;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use super::cache::Cache;
+use super::error::Error;
+use super::filter::FilterPolicy;
+
+// part of the leveldb C API since 1.21, but not declared by leveldb_sys
+#[cfg(feature = "max-file-size")]
+extern "C" {
+    fn leveldb_options_set_max_file_size(o: *mut leveldb_options_t, size: size_t);
+}
+
+// not part of the upstream leveldb C API, only available in patched builds
+#[cfg(feature = "reuse-logs")]
+extern "C" {
+    fn leveldb_options_set_reuse_logs(o: *mut leveldb_options_t, val: u8);
+}
 
 /// Options to consider when opening a new or pre-existing database.
 ///
@@ -11,6 +27,7 @@ use super::cache::Cache;
 ///
 /// For more detailed explanations, consider the
 /// [leveldb documentation](https://github.com/google/leveldb/tree/master/doc)
+#[derive(Clone)]
 pub struct Options {
     /// create the database if missing
     ///
@@ -41,30 +58,98 @@ pub struct Options {
     ///
     /// default: None
     pub block_restart_interval: Option<i32>,
+    /// Override the size up to which leveldb writes a single table file
+    /// before switching to a new one.
+    ///
+    /// Requires the `max-file-size` feature and a leveldb of version 1.21 or
+    /// newer, which exports `leveldb_options_set_max_file_size`.
+    ///
+    /// default: None
+    #[cfg(feature = "max-file-size")]
+    pub max_file_size: Option<size_t>,
     /// Define whether leveldb should write compressed or not.
     ///
     /// default: Compression::No
     pub compression: Compression,
     /// A cache to use during read operations.
     ///
+    /// The same cache can be shared by several databases by cloning it into
+    /// their options, it is freed once the last of them is closed.
+    ///
     /// default: None
     pub cache: Option<Cache>,
+    /// A filter policy to reduce the disk reads of lookups, e.g. a bloom filter.
+    ///
+    /// default: None
+    pub filter_policy: Option<FilterPolicy>,
+    /// Verify checksums on every read, unless its `ReadOptions` clear
+    /// `use_database_default`.
+    ///
+    /// default: false
+    pub default_verify_checksums: bool,
+    /// Repair the database and retry once if opening fails because of
+    /// corruption (see `Error::is_corruption`).
+    ///
+    /// Repairing may drop data that can't be recovered, `Database::was_repaired`
+    /// reports whether it happened. If the retry fails as well, the original
+    /// error is returned.
+    ///
+    /// default: false
+    pub auto_repair: bool,
+    /// Create the parent directories of the database path when opening.
+    ///
+    /// default: false
+    pub create_parents: bool,
+    /// Move the info log, which leveldb writes to the `LOG` file in the
+    /// database directory, to this path once the database is open.
+    ///
+    /// leveldb keeps appending to the moved file, so the path must be on the
+    /// same filesystem as the database. The C API can't install a custom
+    /// logger, this is the only way to relocate the log. A file left at the
+    /// path by a previous session is kept with `.old` appended to its name.
+    ///
+    /// default: None
+    pub info_log_path: Option<PathBuf>,
+    /// Reuse the existing log and manifest files when opening, which makes
+    /// reopening a large database faster.
+    ///
+    /// Requires the `reuse-logs` feature and a leveldb build exporting
+    /// `leveldb_options_set_reuse_logs`.
+    ///
+    /// default: false
+    #[cfg(feature = "reuse-logs")]
+    pub reuse_logs: bool,
 }
 
 impl std::fmt::Debug for Options {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("")
+        let mut tuple = f.debug_tuple("");
+        tuple
             .field(&self.create_if_missing)
             .field(&self.error_if_exists)
             .field(&self.paranoid_checks)
             .field(&self.write_buffer_size)
             .field(&self.max_open_files)
             .field(&self.block_size)
-            .field(&self.block_restart_interval)
-            .finish()
+            .field(&self.block_restart_interval);
+        #[cfg(feature = "max-file-size")]
+        tuple.field(&self.max_file_size);
+        tuple
+            .field(&self.default_verify_checksums)
+            .field(&self.auto_repair)
+            .field(&self.create_parents)
+            .field(&self.info_log_path);
+        #[cfg(feature = "reuse-logs")]
+        tuple.field(&self.reuse_logs);
+        tuple.finish()
     }
 }
 
+const BULK_LOAD_BUFFER_SIZE: size_t = 64 * 1024 * 1024;
+const POINT_LOOKUP_BLOCK_SIZE: size_t = 4 * 1024;
+const POINT_LOOKUP_RESTART_INTERVAL: i32 = 4;
+const POINT_LOOKUP_BLOOM_BITS: i32 = 10;
+
 impl Default for Options {
     fn default() -> Self {
         Self::new()
@@ -82,13 +167,117 @@ impl Options {
             max_open_files: None,
             block_size: None,
             block_restart_interval: None,
+            #[cfg(feature = "max-file-size")]
+            max_file_size: None,
             compression: Compression::No,
             cache: None,
+            filter_policy: None,
+            default_verify_checksums: false,
+            auto_repair: false,
+            create_parents: false,
+            info_log_path: None,
+            #[cfg(feature = "reuse-logs")]
+            reuse_logs: false,
+        }
+    }
+
+    /// Options suited for loading large amounts of data
+    ///
+    /// Uses a 64 MiB write buffer, so that fewer and larger tables are written
+    /// and compacted, and with the `max-file-size` feature 64 MiB table files.
+    /// Data is written uncompressed to keep the write path cheap.
+    pub fn bulk_load_profile() -> Options {
+        Options {
+            write_buffer_size: Some(BULK_LOAD_BUFFER_SIZE),
+            #[cfg(feature = "max-file-size")]
+            max_file_size: Some(BULK_LOAD_BUFFER_SIZE),
+            compression: Compression::No,
+            ..Options::new()
+        }
+    }
+
+    /// Options suited for reading single keys, with a cache of `cache_mb` MiB
+    ///
+    /// Adds a bloom filter of 10 bits per key, so that lookups of missing keys
+    /// rarely hit the disk, and uses small blocks with frequent restart points,
+    /// so that a lookup reads and scans little data per block.
+    pub fn point_lookup_profile(cache_mb: size_t) -> Options {
+        Options {
+            block_size: Some(POINT_LOOKUP_BLOCK_SIZE),
+            block_restart_interval: Some(POINT_LOOKUP_RESTART_INTERVAL),
+            cache: Some(Cache::mb(cache_mb)),
+            filter_policy: Some(FilterPolicy::bloom(POINT_LOOKUP_BLOOM_BITS)),
+            ..Options::new()
+        }
+    }
+
+    /// Check the options against the limits of the process
+    ///
+    /// On Unix, `max_open_files` must not exceed the soft limit on open file
+    /// descriptors (`RLIMIT_NOFILE`), otherwise leveldb fails with "too many
+    /// open files" once the database grows. Other platforms are not checked.
+    pub fn validate(&self) -> Result<(), Error> {
+        #[cfg(unix)]
+        {
+            if let Some(max_open_files) = self.max_open_files {
+                let limit = open_files_limit()?;
+                if max_open_files > 0 && (max_open_files as libc::rlim_t) > limit {
+                    return Err(Error::new(format!(
+                        "max_open_files of {} exceeds the limit of {} open files",
+                        max_open_files, limit
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// the soft limit on open file descriptors of this process
+#[cfg(unix)]
+fn open_files_limit() -> Result<libc::rlim_t, Error> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(limit.rlim_cur)
+}
+
+/// Options for `Database::open_with`
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    /// The options the database is opened with.
+    pub options: Options,
+    /// How long to keep retrying while the database lock is held, e.g. by
+    /// another handle that is about to be closed.
+    ///
+    /// Only live handles hold the lock, a `LOCK` file left behind by a
+    /// crashed process never blocks opening.
+    ///
+    /// default: None, failing immediately
+    pub open_timeout: Option<Duration>,
+}
+
+impl OpenOptions {
+    /// Return an `OpenOptions` struct opening with `options` and no timeout.
+    pub fn new(options: Options) -> OpenOptions {
+        OpenOptions {
+            options,
+            open_timeout: None,
         }
     }
 }
 
 /// The write options to use for a write operation.
+///
+/// leveldb always appends writes to its write-ahead log, the C API offers no
+/// way to disable it. For initial imports consider `Database::bulk_load` together
+/// with a larger `Options::write_buffer_size` instead.
 #[derive(Copy, Clone, Debug)]
 pub struct WriteOptions {
     /// `fsync` before acknowledging a write operation.
@@ -108,6 +297,25 @@ impl WriteOptions {
     pub fn new() -> WriteOptions {
         WriteOptions { sync: false }
     }
+
+    /// Write options acknowledging writes before they are synced to disk
+    ///
+    /// Same as `new`. Writes survive a crash of the process, but may be lost
+    /// when the machine crashes.
+    pub fn fast() -> WriteOptions {
+        WriteOptions::new()
+    }
+
+    /// Write options syncing every write to disk before acknowledging it
+    pub fn durable() -> WriteOptions {
+        WriteOptions { sync: true }
+    }
+
+    /// Set whether writes are synced to disk before being acknowledged
+    pub fn with_sync(mut self, sync: bool) -> WriteOptions {
+        self.sync = sync;
+        self
+    }
 }
 
 /// The read options to use for any read operation.
@@ -117,6 +325,14 @@ pub struct ReadOptions {
     ///
     /// default: false
     pub verify_checksums: bool,
+    /// Also verify checksums if the database was opened with
+    /// `Options::default_verify_checksums`.
+    ///
+    /// Clear it, e.g. with `with_verify_checksums`, to skip verification
+    /// regardless of the database default.
+    ///
+    /// default: true
+    pub use_database_default: bool,
     /// Whether to fill the internal cache with the
     /// results of the read.
     ///
@@ -135,9 +351,40 @@ impl ReadOptions {
     pub fn new() -> ReadOptions {
         ReadOptions {
             verify_checksums: false,
+            use_database_default: true,
+            fill_cache: true,
+        }
+    }
+
+    /// Read options suited for looking up single keys
+    ///
+    /// Fills the cache, so that repeated reads of hot keys are served from memory.
+    pub fn point_read() -> ReadOptions {
+        ReadOptions {
+            verify_checksums: false,
+            use_database_default: true,
             fill_cache: true,
         }
     }
+
+    /// Read options suited for large scans
+    ///
+    /// Does not fill the cache, so that a scan does not evict the working set,
+    /// and skips checksum verification regardless of the database default.
+    pub fn scan() -> ReadOptions {
+        ReadOptions {
+            verify_checksums: false,
+            use_database_default: false,
+            fill_cache: false,
+        }
+    }
+
+    /// Set whether checksums are verified, overriding the database default
+    pub fn with_verify_checksums(mut self, verify_checksums: bool) -> ReadOptions {
+        self.verify_checksums = verify_checksums;
+        self.use_database_default = false;
+        self
+    }
 }
 
 #[allow(missing_docs)]
@@ -162,13 +409,22 @@ pub unsafe fn c_options(
     if let Some(bi) = options.block_restart_interval {
         leveldb_options_set_block_restart_interval(c_options, bi);
     }
+    #[cfg(feature = "max-file-size")]
+    if let Some(fs) = options.max_file_size {
+        leveldb_options_set_max_file_size(c_options, fs);
+    }
     leveldb_options_set_compression(c_options, options.compression);
+    #[cfg(feature = "reuse-logs")]
+    leveldb_options_set_reuse_logs(c_options, options.reuse_logs as u8);
     if let Some(c) = comparator {
         leveldb_options_set_comparator(c_options, c);
     }
     if let Some(ref cache) = options.cache {
         leveldb_options_set_cache(c_options, cache.raw_ptr());
     }
+    if let Some(ref policy) = options.filter_policy {
+        leveldb_options_set_filter_policy(c_options, policy.raw_ptr());
+    }
     c_options
 }
 
@@ -11,7 +11,12 @@ use super::iterator::{Iterable, Iterator, KeyIterator, ValueIterator};
 use super::key::IntoLevelDBKey;
 use super::options::{c_readoptions, ReadOptions};
 use libc::{c_char, size_t};
+use std::collections::BTreeMap;
+use std::iter;
 use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[allow(missing_docs)]
 struct RawSnapshot {
@@ -25,20 +30,71 @@ impl Drop for RawSnapshot {
     }
 }
 
+/// The snapshots of a database that are still alive, by id
+#[derive(Debug, Default)]
+pub(crate) struct SnapshotRegistry {
+    next_id: AtomicU64,
+    live: Mutex<BTreeMap<u64, Instant>>,
+}
+
+impl SnapshotRegistry {
+    fn register(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.live.lock().unwrap().insert(id, Instant::now());
+        id
+    }
+
+    fn release(&self, id: u64) {
+        self.live.lock().unwrap().remove(&id);
+    }
+}
+
+/// A snapshot that was not released yet, see `Database::outstanding_snapshots`
+#[derive(Clone, Debug)]
+pub struct SnapshotInfo {
+    /// Identifies the snapshot among the snapshots of its database
+    pub id: u64,
+    /// When the snapshot was taken
+    pub created: Instant,
+    /// How long the snapshot has been alive
+    pub age: Duration,
+}
+
 /// A database snapshot
 ///
 /// Represents a database at a certain point in time,
 /// and allows for all read operations (get and iteration).
+///
+/// The snapshot is released exactly once, when it is dropped. It borrows
+/// the database it was taken from, so it can't outlive it:
+///
+/// ```compile_fail
+/// use leveldb::database::Database;
+/// use leveldb::options::Options;
+/// use leveldb::snapshots::Snapshots;
+/// use std::path::Path;
+///
+/// let snapshot = {
+///     let database = Database::open(Path::new("db"), &Options::new()).unwrap();
+///     database.snapshot()
+/// };
+/// ```
 pub struct Snapshot<'a> {
     raw: RawSnapshot,
     database: &'a Database,
+    id: u64,
+}
+
+impl Drop for Snapshot<'_> {
+    fn drop(&mut self) {
+        self.database.snapshots.release(self.id);
+    }
 }
 
 impl<'a> Snapshot<'a> {
     /// fetches a key from the database
     ///
     /// Inserts this snapshot into ReadOptions before reading
-
     pub fn get(
         &self,
         options: &ReadOptions,
@@ -53,7 +109,7 @@ impl<'a> Snapshot<'a> {
         unsafe {
             let mut error = ptr::null_mut();
             let mut length: size_t = 0;
-            let c_readoptions = c_readoptions(options);
+            let c_readoptions = c_readoptions(&self.database.effective_read_options(options));
 
             // add the extra snapshot information to c_readoptions
             leveldb_readoptions_set_snapshot(c_readoptions, self.raw_ptr());
@@ -74,7 +130,7 @@ impl<'a> Snapshot<'a> {
 
                 Ok(bytes_opt.map(|val| val.into()))
             } else {
-                Err(Error::new_from_char(error))
+                Err(Error::new_from_char(error).for_key(key))
             }
         }
     }
@@ -91,11 +147,112 @@ impl<'a> Snapshot<'a> {
 pub trait Snapshots {
     /// Creates a snapshot and returns a struct
     /// representing it.
-    fn snapshot(&self) -> Snapshot;
+    fn snapshot(&self) -> Snapshot<'_>;
+
+    /// Creates a snapshot and iterates over it
+    ///
+    /// The returned scan owns the snapshot, which is released
+    /// together with the iterator.
+    fn scan_consistent(&self, options: &ReadOptions) -> ConsistentScan<'_> {
+        let snapshot = self.snapshot();
+        // the scan keeps the snapshot alive as long as the iterator
+        let iter = unsafe {
+            Iterator::with_raw_snapshot(snapshot.database, options, Some(snapshot.raw_ptr()))
+        };
+
+        ConsistentScan { iter, snapshot }
+    }
+}
+
+/// An iteration over (key, value) pairs of a snapshot it owns.
+pub struct ConsistentScan<'a> {
+    // declared before the snapshot, so the iterator is dropped first
+    iter: Iterator<'a>,
+    snapshot: Snapshot<'a>,
+}
+
+impl<'a> ConsistentScan<'a> {
+    /// The snapshot the scan iterates over.
+    pub fn snapshot(&self) -> &Snapshot<'a> {
+        &self.snapshot
+    }
+}
+
+impl<'a> iter::Iterator for ConsistentScan<'a> {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// Snapshot iteration over a shared database, without borrowing it.
+pub trait SharedSnapshots {
+    /// Creates a snapshot and iterates over it
+    ///
+    /// The returned iterator owns the snapshot and a handle to the database,
+    /// so it can be returned from functions and outlive the borrow of the
+    /// database it was created from.
+    fn owned_snapshot_iter(&self, options: &ReadOptions) -> OwnedSnapshotIter;
+}
+
+/// An iteration over (key, value) pairs of a snapshot, owning the snapshot
+/// and a handle to its database, see `SharedSnapshots::owned_snapshot_iter`.
+pub struct OwnedSnapshotIter {
+    // fields are dropped in declaration order: the iterator before the
+    // snapshot, and both before the database handle
+    iter: Iterator<'static>,
+    // only held to release the snapshot once the iterator is done with it
+    #[allow(dead_code)]
+    snapshot: RawSnapshot,
+    id: u64,
+    database: Arc<Database>,
+}
+
+impl Drop for OwnedSnapshotIter {
+    fn drop(&mut self) {
+        self.database.snapshots.release(self.id);
+    }
+}
+
+impl iter::Iterator for OwnedSnapshotIter {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n)
+    }
+}
+
+impl SharedSnapshots for Arc<Database> {
+    fn owned_snapshot_iter(&self, options: &ReadOptions) -> OwnedSnapshotIter {
+        let database = Arc::clone(self);
+        let snapshot = RawSnapshot {
+            db_ptr: database.database.ptr,
+            ptr: unsafe { leveldb_create_snapshot(database.database.ptr) },
+        };
+
+        // the iterator borrows nothing but the database, which the
+        // `Arc` keeps alive for as long as the iterator
+        let iter = unsafe {
+            let shared: &'static Database = &*Arc::as_ptr(&database);
+            Iterator::with_raw_snapshot(shared, options, Some(snapshot.ptr))
+        };
+
+        OwnedSnapshotIter {
+            iter,
+            snapshot,
+            id: database.snapshots.register(),
+            database,
+        }
+    }
 }
 
 impl Snapshots for Database {
-    fn snapshot(&self) -> Snapshot {
+    fn snapshot(&self) -> Snapshot<'_> {
         let db_str = self.database.ptr;
         let snap = unsafe { leveldb_create_snapshot(db_str) };
 
@@ -107,10 +264,30 @@ impl Snapshots for Database {
         Snapshot {
             raw,
             database: self,
+            id: self.snapshots.register(),
         }
     }
 }
 
+impl Database {
+    /// The snapshots taken that are still alive, oldest first
+    ///
+    /// Live snapshots keep leveldb from discarding overwritten data during
+    /// compactions, long-lived ones usually point at a leaked handle.
+    pub fn outstanding_snapshots(&self) -> Vec<SnapshotInfo> {
+        let now = Instant::now();
+        let live = self.snapshots.live.lock().unwrap();
+
+        live.iter()
+            .map(|(&id, &created)| SnapshotInfo {
+                id,
+                created,
+                age: now.saturating_duration_since(created),
+            })
+            .collect()
+    }
+}
+
 impl<'a> Iterable<'a> for Snapshot<'a> {
     fn iter(&'a self, options: &ReadOptions) -> Iterator<'a> {
         Iterator::new(self.database, options, Some(self))
@@ -0,0 +1,127 @@
+//! Optimistic transactions
+//!
+//! A transaction reads from a snapshot and stages its writes. On commit
+//! the writes are applied as one batch, unless one of the keys read was
+//! changed since the snapshot was taken.
+use super::batch::{Batch, WriteBatch};
+use super::db::Database;
+use super::error::Error;
+use super::key::IntoLevelDBKey;
+use super::options::{ReadOptions, WriteOptions};
+use super::snapshots::{Snapshot, Snapshots};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// An optimistic transaction over a database
+///
+/// Reads see the database as of `Transaction::new`, plus the writes staged
+/// in the transaction itself. Nothing is written before `commit`, which
+/// fails with a conflict (see `Error::is_conflict`) if a key read by the
+/// transaction was changed in the meantime.
+///
+/// Commits of transactions on the same database are serialized, plain
+/// writes done between the check and the write of a commit are not
+/// detected.
+pub struct Transaction<'a> {
+    snapshot: Snapshot<'a>,
+    database: &'a Database,
+    // the value of every key read from the snapshot
+    reads: RefCell<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+    // the staged value of every key written, `None` for deletes
+    writes: RefCell<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Start a transaction reading from a new snapshot of `database`
+    pub fn new(database: &'a Database) -> Transaction<'a> {
+        Transaction {
+            snapshot: database.snapshot(),
+            database,
+            reads: RefCell::new(BTreeMap::new()),
+            writes: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// The snapshot the transaction reads from
+    pub fn snapshot(&self) -> &Snapshot<'a> {
+        &self.snapshot
+    }
+
+    /// Read a key, see `get_u8`
+    pub fn get(
+        &self,
+        options: &ReadOptions,
+        key: &dyn IntoLevelDBKey,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        key.as_u8_slice_for_get(&|k| self.get_u8(options, k))
+    }
+
+    /// Read a key
+    ///
+    /// Returns the value staged by this transaction if there is one,
+    /// otherwise the value in the snapshot, which is checked again on commit.
+    pub fn get_u8(&self, options: &ReadOptions, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        if let Some(staged) = self.writes.borrow().get(key) {
+            return Ok(staged.clone());
+        }
+        if let Some(seen) = self.reads.borrow().get(key) {
+            return Ok(seen.clone());
+        }
+
+        let value = self.snapshot.get_u8(options, key)?;
+        self.reads.borrow_mut().insert(key.to_vec(), value.clone());
+        Ok(value)
+    }
+
+    /// Stage writing `value` for `key`
+    pub fn put(&self, key: &dyn IntoLevelDBKey, value: &[u8]) {
+        let _ = key.as_u8_slice_for_write(&|k| {
+            self.put_u8(k, value);
+            Ok(())
+        });
+    }
+
+    /// Stage writing `value` for `key`
+    pub fn put_u8(&self, key: &[u8], value: &[u8]) {
+        self.writes
+            .borrow_mut()
+            .insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    /// Stage deleting `key`
+    pub fn delete(&self, key: &dyn IntoLevelDBKey) {
+        let _ = key.as_u8_slice_for_write(&|k| {
+            self.delete_u8(k);
+            Ok(())
+        });
+    }
+
+    /// Stage deleting `key`
+    pub fn delete_u8(&self, key: &[u8]) {
+        self.writes.borrow_mut().insert(key.to_vec(), None);
+    }
+
+    /// Write the staged writes as one batch
+    ///
+    /// Fails without writing anything if the current value of a key read
+    /// by the transaction differs from the value it read.
+    pub fn commit(self, options: &WriteOptions) -> Result<(), Error> {
+        let _commit = self.database.commit_lock.lock().unwrap();
+
+        let read_options = ReadOptions::new();
+        for (key, seen) in self.reads.borrow().iter() {
+            if self.database.get_u8(&read_options, key)? != *seen {
+                return Err(Error::conflict(key));
+            }
+        }
+
+        let batch = WriteBatch::new();
+        for (key, value) in self.writes.borrow().iter() {
+            match value {
+                Some(value) => batch.put_u8(key, value),
+                None => batch.delete_u8(key),
+            }
+        }
+        self.database.write(options, &batch)
+    }
+}
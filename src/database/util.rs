@@ -1,12 +1,97 @@
+use super::error::Error;
+use std::fmt;
+
 pub trait FromU8 {
     fn from_u8(data: &[u8]) -> Self;
 }
 
+/// Fallible counterpart of `FromU8`, for data that may be corrupt or truncated
+pub trait TryFromU8: Sized {
+    fn try_from_u8(data: &[u8]) -> Result<Self, ConversionError>;
+}
+
+/// The byte order of an encoded integer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+/// Integer conversions with an explicit byte order
+///
+/// `FromU8` decodes big-endian, matching the keys written with `to_be_bytes`,
+/// these methods additionally allow reading and writing little-endian data.
+/// Decoding panics on slices of the wrong length, like `FromU8`.
+pub trait EndianU8: Sized {
+    fn from_u8_be(data: &[u8]) -> Self;
+    fn from_u8_le(data: &[u8]) -> Self;
+    fn to_u8_be(&self) -> Vec<u8>;
+    fn to_u8_le(&self) -> Vec<u8>;
+}
+
+/// The error returned when a byte slice has the wrong length for the target type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    /// The name of the target type
+    pub target: &'static str,
+    /// The number of bytes the target type is decoded from
+    pub expected: usize,
+    /// The number of bytes given
+    pub actual: usize,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot decode {} from {} bytes, expected {}",
+            self.target, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
 macro_rules! impl_from_u8_for_int {
     ($T: ty, $N: expr) => {
         impl FromU8 for $T {
             fn from_u8(data: &[u8]) -> $T {
-                assert_eq!(data.len(), $N);
+                match <$T>::try_from_u8(data) {
+                    Ok(value) => value,
+                    Err(err) => panic!("{}", err),
+                }
+            }
+        }
+
+        impl EndianU8 for $T {
+            fn from_u8_be(data: &[u8]) -> $T {
+                <$T>::from_u8(data)
+            }
+
+            fn from_u8_le(data: &[u8]) -> $T {
+                let mut reversed = data.to_vec();
+                reversed.reverse();
+                <$T>::from_u8(&reversed)
+            }
+
+            fn to_u8_be(&self) -> Vec<u8> {
+                self.to_be_bytes().to_vec()
+            }
+
+            fn to_u8_le(&self) -> Vec<u8> {
+                self.to_le_bytes().to_vec()
+            }
+        }
+
+        impl TryFromU8 for $T {
+            fn try_from_u8(data: &[u8]) -> Result<$T, ConversionError> {
+                if data.len() != $N {
+                    return Err(ConversionError {
+                        target: stringify!($T),
+                        expected: $N,
+                        actual: data.len(),
+                    });
+                }
 
                 let mut value: $T = 0;
 
@@ -14,7 +99,7 @@ macro_rules! impl_from_u8_for_int {
                     value |= (data[i] as $T) << 8 * ($N - i - 1);
                 }
 
-                value
+                Ok(value)
             }
         }
     };
@@ -30,3 +115,38 @@ impl_from_u8_for_int!(u64, 8);
 impl_from_u8_for_int!(i64, 8);
 impl_from_u8_for_int!(u128, 16);
 impl_from_u8_for_int!(i128, 16);
+
+/// Encode `bytes` as lowercase hex, two digits per byte
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+/// Decode a hex string, e.g. a key given on the command line
+///
+/// Upper and lower case digits are accepted. Strings of odd length or with
+/// characters other than hex digits are rejected.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    if hex.len() & 1 != 0 {
+        return Err(Error::new(format!(
+            "invalid hex string {:?}: odd number of digits",
+            hex
+        )));
+    }
+
+    let digit = |c: u8| {
+        (c as char).to_digit(16).ok_or_else(|| {
+            Error::new(format!(
+                "invalid hex string {:?}: {:?} is not a hex digit",
+                hex, c as char
+            ))
+        })
+    };
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+        .collect()
+}
@@ -9,11 +9,13 @@ pub use database::compaction;
 pub use database::comparator;
 pub use database::db;
 pub use database::error;
+pub use database::filter;
 pub use database::iterator;
 pub use database::key;
 pub use database::management;
 pub use database::options;
 pub use database::snapshots;
+pub use database::transaction;
 pub use database::util;
 
 use leveldb_sys::{leveldb_major_version, leveldb_minor_version};
@@ -21,7 +23,6 @@ use leveldb_sys::{leveldb_major_version, leveldb_minor_version};
 /// Library version information
 ///
 /// Need a recent version of leveldb to be used.
-
 pub trait Version {
     /// The major version.
     fn major() -> isize {
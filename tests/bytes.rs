@@ -0,0 +1,59 @@
+use leveldb::database::bytes::{next_key, prefix_successor, prev_key, DisplayKey};
+
+#[test]
+fn test_prefix_successor() {
+    assert_eq!(prefix_successor(&[1, 2, 3]), Some(vec![1, 2, 4]));
+    assert_eq!(prefix_successor(b"user/"), Some(b"user0".to_vec()));
+}
+
+#[test]
+fn test_prefix_successor_trailing_ff() {
+    assert_eq!(prefix_successor(&[1, 2, 0xFF]), Some(vec![1, 3]));
+    assert_eq!(prefix_successor(&[1, 0xFF, 0xFF]), Some(vec![2]));
+}
+
+#[test]
+fn test_prefix_successor_all_ff() {
+    assert_eq!(prefix_successor(&[0xFF, 0xFF]), None);
+    assert_eq!(prefix_successor(&[]), None);
+}
+
+#[test]
+fn test_next_key() {
+    assert_eq!(next_key(b"user"), b"user\0".to_vec());
+    assert_eq!(next_key(&[1, 0xFF]), vec![1, 0xFF, 0]);
+    assert_eq!(next_key(&[1, 0]), vec![1, 0, 0]);
+    assert_eq!(next_key(&[]), vec![0]);
+    assert!(next_key(b"user").as_slice() > &b"user"[..]);
+}
+
+#[test]
+fn test_prev_key() {
+    assert_eq!(prev_key(&[1, 2, 3]), Some(vec![1, 2, 2]));
+    assert_eq!(prev_key(&[1, 0xFF]), Some(vec![1, 0xFE]));
+    assert_eq!(prev_key(&[1]), Some(vec![0]));
+    assert!(prev_key(b"user").unwrap().as_slice() < &b"user"[..]);
+}
+
+#[test]
+fn test_prev_key_trailing_zero() {
+    assert_eq!(prev_key(&[1, 2, 0]), Some(vec![1, 2]));
+    assert_eq!(prev_key(&[0, 0]), Some(vec![0]));
+    assert_eq!(prev_key(&[0]), Some(vec![]));
+    for key in [&b"user"[..], &[1, 0xFF], &[]] {
+        assert_eq!(prev_key(&next_key(key)), Some(key.to_vec()));
+    }
+}
+
+#[test]
+fn test_prev_key_empty() {
+    assert_eq!(prev_key(&[]), None);
+}
+
+#[test]
+fn test_display_key() {
+    assert_eq!(DisplayKey(b"user/42").to_string(), "user/42");
+    assert_eq!(DisplayKey(&[0, 0, 1, 0xff]).to_string(), "hex:000001ff");
+    assert_eq!(DisplayKey(&[0, 0, 0, 1]).to_string(), "hex:00000001");
+    assert_eq!(DisplayKey(b"").to_string(), "");
+}
@@ -1,7 +1,11 @@
-use crate::utils::temp_dir;
+use crate::utils::{db_put_simple, temp_dir};
+use leveldb::compaction::Compaction;
 use leveldb::database::cache::Cache;
 use leveldb::database::Database;
-use leveldb::options::Options;
+use leveldb::options::{Options, ReadOptions};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 #[test]
 fn test_open_database_with_cache() {
@@ -14,3 +18,91 @@ fn test_open_database_with_cache() {
 
     assert!(res.is_ok());
 }
+
+#[test]
+fn test_shared_cache() {
+    let cache = Cache::new(1024 * 1024);
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.cache = Some(cache.clone());
+
+    let tmp1 = temp_dir("shared_cache_1");
+    let tmp2 = temp_dir("shared_cache_2");
+    let db1 = Database::open(tmp1.path(), &opts).unwrap();
+    let db2 = Database::open(tmp2.path(), &opts).unwrap();
+    drop(opts);
+    drop(cache);
+
+    db_put_simple(&db1, &1, &[1]);
+    db_put_simple(&db2, &2, &[2]);
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(db1.get(&read_opts, &1).unwrap(), Some(vec![1]));
+    assert_eq!(db2.get(&read_opts, &2).unwrap(), Some(vec![2]));
+    assert_eq!(db1.get(&read_opts, &2).unwrap(), None);
+
+    drop(db1);
+    assert_eq!(db2.get(&read_opts, &2).unwrap(), Some(vec![2]));
+}
+
+#[test]
+fn test_concurrent_cached_reads() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.cache = Some(Cache::new(8 * 1024 * 1024));
+
+    let tmp = temp_dir("concurrent_cached_reads");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    for i in 0..10_000u32 {
+        db_put_simple(&database, &i, &i.to_le_bytes());
+    }
+    database.compact(&[], &[0xff; 4]);
+
+    let threads = 4;
+    let reads_per_thread = 20_000u32;
+    let database = Arc::new(database);
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|t| {
+            let database = database.clone();
+            thread::spawn(move || {
+                let read_opts = ReadOptions::new();
+                for n in 0..reads_per_thread {
+                    let i = n.wrapping_mul(7_919).wrapping_add(t) % 10_000;
+                    let value = database.get(&read_opts, &i).unwrap();
+                    assert_eq!(value, Some(i.to_le_bytes().to_vec()));
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // run with --nocapture to see the throughput
+    let reads = (threads * reads_per_thread) as f64;
+    eprintln!(
+        "{} threads: {:.0} cached reads/s",
+        threads,
+        reads / start.elapsed().as_secs_f64()
+    );
+}
+
+#[test]
+fn test_cache_constructors() {
+    let caches = vec![Cache::default(), Cache::kb(512), Cache::mb(16)];
+
+    for (i, cache) in caches.into_iter().enumerate() {
+        let mut opts = Options::new();
+        opts.create_if_missing = true;
+        opts.cache = Some(cache);
+
+        let tmp = temp_dir(&format!("cache_constructors_{}", i));
+        let database = Database::open(tmp.path(), &opts).unwrap();
+        db_put_simple(&database, &1, &[1]);
+        assert_eq!(
+            database.get(&ReadOptions::new(), &1).unwrap(),
+            Some(vec![1])
+        );
+    }
+}
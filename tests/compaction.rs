@@ -1,5 +1,10 @@
 use crate::utils::{db_put_u8_simple, open_database, temp_dir};
-use leveldb::compaction::Compaction;
+use leveldb::batch::{Batch, WriteBatch};
+use leveldb::compaction::{stats_need_compaction, Compaction, CompactionTracker, FilterDecision};
+use leveldb::database::Database;
+use leveldb::iterator::Iterable;
+use leveldb::options::{Options, ReadOptions, WriteOptions};
+use std::fs;
 
 #[test]
 fn test_iterator_from_to() {
@@ -13,3 +18,134 @@ fn test_iterator_from_to() {
 
     database.compact(&[2], &[4]);
 }
+
+#[test]
+fn test_filter_compact() {
+    let tmp = temp_dir("filter_compact");
+    let database = &mut open_database(tmp.path(), true);
+    let now = 1_000u64;
+    // values hold an expiry timestamp
+    for (key, expires) in [(1u8, 500u64), (2, 1_500), (3, 900), (4, 2_000), (5, 10)] {
+        db_put_u8_simple(database, &[key], &expires.to_be_bytes());
+    }
+
+    database
+        .filter_compact(&[2], &[4], |_, value| {
+            let mut expires = [0u8; 8];
+            expires.copy_from_slice(value);
+            if u64::from_be_bytes(expires) < now {
+                FilterDecision::Remove
+            } else {
+                FilterDecision::Keep
+            }
+        })
+        .unwrap();
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.keys_iter(&read_opts).collect();
+    assert_eq!(keys, vec![vec![1], vec![2], vec![4], vec![5]]);
+
+    database
+        .filter_compact(&[1], &[1], |_, _| FilterDecision::Change(vec![0]))
+        .unwrap();
+    assert_eq!(database.get_u8(&read_opts, &[1]).unwrap(), Some(vec![0]));
+    assert_eq!(
+        database.get_u8(&read_opts, &[2]).unwrap(),
+        Some(1_500u64.to_be_bytes().to_vec())
+    );
+}
+
+#[test]
+fn test_compaction_pending() {
+    let tmp = temp_dir("compaction_pending");
+    let database = open_database(tmp.path(), true);
+    assert!(!database.compaction_pending());
+
+    for i in 0..100u8 {
+        db_put_u8_simple(&database, &[i], &[0; 100]);
+    }
+    database.compact(&[0], &[100]);
+    assert!(!database.compaction_pending());
+}
+
+#[test]
+fn test_stats_need_compaction() {
+    let stats = |rows: &str| {
+        format!(
+            "                               Compactions\n\
+             Level  Files Size(MB) Time(sec) Read(MB) Write(MB)\n\
+             --------------------------------------------------\n{}",
+            rows
+        )
+    };
+
+    assert!(!stats_need_compaction(&stats("")));
+    assert!(!stats_need_compaction(&stats(
+        "  0        3        0         0        0         0\n\
+           1        2        9         0        0         9\n\
+           2        5       99         0        0        99\n"
+    )));
+    // too many files at level 0
+    assert!(stats_need_compaction(&stats(
+        "  0        4        0         0        0         0\n"
+    )));
+    // level 2 holds more than 100 MB
+    assert!(stats_need_compaction(&stats(
+        "  1        2        9         0        0         9\n\
+           2       60      101         0        0       101\n"
+    )));
+}
+
+#[test]
+fn test_compaction_tracker() {
+    let tmp = temp_dir("compaction_tracker");
+    let mut options = Options::new();
+    options.create_if_missing = true;
+    options.write_buffer_size = Some(64 * 1024);
+    let database = Database::open(tmp.path(), &options).unwrap();
+
+    let mut tracker = CompactionTracker::new(&database).unwrap();
+    assert_eq!(tracker.bytes_written_delta(), 0);
+    assert_eq!(tracker.compactions_delta(), 0);
+
+    let batch = WriteBatch::new();
+    let value = vec![7u8; 1024];
+    for i in 0..4_000u32 {
+        batch.put_u8(&i.wrapping_mul(2_654_435_761).to_be_bytes(), &value);
+    }
+    database.write(&WriteOptions::new(), &batch).unwrap();
+    database.compact(&[], &[0xff; 4]);
+
+    tracker.sample().unwrap();
+    assert!(tracker.bytes_written_delta() > 0);
+    assert!(tracker.compactions_delta() > 0);
+
+    // nothing happened since the last sample
+    tracker.sample().unwrap();
+    assert_eq!(tracker.bytes_written_delta(), 0);
+    assert_eq!(tracker.compactions_delta(), 0);
+}
+
+#[test]
+fn test_compaction_tracker_rotated_log() {
+    let tmp = temp_dir("compaction_tracker_rotated");
+    let logs = temp_dir("compaction_tracker_rotated_logs");
+    let log = logs.path().join("leveldb.log");
+    let mut options = Options::new();
+    options.create_if_missing = true;
+    options.info_log_path = Some(log.clone());
+    let database = Database::open(tmp.path(), &options).unwrap();
+
+    let mut tracker = CompactionTracker::new(&database).unwrap();
+    db_put_u8_simple(&database, b"key", b"value");
+    database.compact(&[], &[0xff]);
+    tracker.sample().unwrap();
+    assert!(tracker.compactions_delta() > 0);
+
+    // a shorter log replacing the one read so far is counted from its start
+    fs::rename(&log, logs.path().join("leveldb.log.old")).unwrap();
+    let moved = "2024/01/01-00:00:00.000000 1 Moved #7 to level-1 100 bytes OK\n";
+    fs::write(&log, moved).unwrap();
+    tracker.sample().unwrap();
+    assert_eq!(tracker.compactions_delta(), 1);
+}
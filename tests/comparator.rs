@@ -1,10 +1,13 @@
 use crate::utils::{db_put_u8_simple, temp_dir};
-use leveldb::comparator::Comparator;
+use leveldb::compaction::Compaction;
+use leveldb::comparator::{Comparator, NumericPrefixComparator, SortOrder, TupleComparator};
 use leveldb::database::Database;
 use leveldb::iterator::Iterable;
 use leveldb::options::{Options, ReadOptions};
+use leveldb::util::Endian;
 use libc::c_char;
 use std::cmp::Ordering;
+use std::fs;
 
 struct ReverseComparator;
 
@@ -37,3 +40,159 @@ fn test_comparator() {
     assert_eq!((vec![2], vec![2]), iter.next().unwrap());
     assert_eq!((vec![1], vec![1]), iter.next().unwrap());
 }
+
+#[test]
+fn test_comparator_across_blocks() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.block_size = Some(256);
+    opts.write_buffer_size = Some(64 * 1024);
+
+    let tmp = temp_dir("reverse_comparator_blocks");
+    let database = Database::open_with_comparator(tmp.path(), &opts, ReverseComparator).unwrap();
+
+    let value = [0u8; 64];
+    for i in 0..2000u32 {
+        db_put_u8_simple(&database, &i.to_be_bytes(), &value);
+    }
+    database.compact(&2000u32.to_be_bytes(), &0u32.to_be_bytes());
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.keys_iter(&read_opts).collect();
+    let expected: Vec<_> = (0..2000u32)
+        .rev()
+        .map(|i| i.to_be_bytes().to_vec())
+        .collect();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn test_numeric_prefix_comparator() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("numeric_prefix_comparator");
+    let comparator = NumericPrefixComparator::new(2, Endian::Little);
+    let database = &mut Database::open_with_comparator(tmp.path(), &opts, comparator).unwrap();
+
+    db_put_u8_simple(database, &[0, 1, b'a'], &[1]);
+    db_put_u8_simple(database, &[2, 0, b'b'], &[2]);
+    db_put_u8_simple(database, &[2, 0, b'a'], &[3]);
+    db_put_u8_simple(database, &[1, 0], &[4]);
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<Vec<u8>> = database.keys_iter(&read_opts).collect();
+    // bytewise order would start with the key of id 256
+    assert_eq!(
+        keys,
+        vec![
+            vec![1, 0],
+            vec![2, 0, b'a'],
+            vec![2, 0, b'b'],
+            vec![0, 1, b'a']
+        ]
+    );
+}
+
+#[test]
+fn test_numeric_prefix_comparator_name() {
+    let opts = Options::new();
+    let tmp = temp_dir("numeric_prefix_comparator_name");
+    let mut create_opts = Options::new();
+    create_opts.create_if_missing = true;
+    let comparator = NumericPrefixComparator::new(8, Endian::Little);
+    drop(Database::open_with_comparator(tmp.path(), &create_opts, comparator).unwrap());
+
+    let comparator = NumericPrefixComparator::new(8, Endian::Big);
+    assert!(Database::open_with_comparator(tmp.path(), &opts, comparator).is_err());
+    let comparator = NumericPrefixComparator::new(8, Endian::Little);
+    assert!(Database::open_with_comparator(tmp.path(), &opts, comparator).is_ok());
+}
+
+#[test]
+fn test_stored_comparator_name() {
+    let tmp = temp_dir("stored_comparator_name");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let comparator = NumericPrefixComparator::new(8, Endian::Little);
+    let database = Database::open_with_comparator(tmp.path(), &opts, comparator).unwrap();
+    db_put_u8_simple(&database, &[1], &[1]);
+    drop(database);
+
+    let stored = Database::stored_comparator_name(tmp.path()).unwrap();
+    assert_eq!(stored, "leveldb.NumericPrefixComparator.8.le");
+
+    let err = Database::open(tmp.path(), &Options::new()).unwrap_err();
+    let mismatch = err.comparator_mismatch().unwrap();
+    assert_eq!(mismatch.expected, stored);
+    assert_eq!(mismatch.found, "leveldb.BytewiseComparator");
+}
+
+#[test]
+fn test_stored_comparator_name_default() {
+    let tmp = temp_dir("stored_comparator_name_default");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    drop(Database::open(tmp.path(), &opts).unwrap());
+
+    assert_eq!(
+        Database::stored_comparator_name(tmp.path()).unwrap(),
+        "leveldb.BytewiseComparator"
+    );
+    assert!(Database::stored_comparator_name(&tmp.path().join("missing")).is_err());
+}
+
+#[test]
+fn test_stored_comparator_name_torn_manifest() {
+    let tmp = temp_dir("stored_comparator_name_torn");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    drop(Database::open(tmp.path(), &opts).unwrap());
+
+    // a record header whose data wasn't written yet ends the manifest
+    let current = fs::read_to_string(tmp.path().join("CURRENT")).unwrap();
+    let manifest = tmp.path().join(current.trim_end());
+    let mut data = fs::read(&manifest).unwrap();
+    data.extend_from_slice(&[0, 0, 0, 0, 100, 0, 1]);
+    fs::write(&manifest, data).unwrap();
+
+    assert_eq!(
+        Database::stored_comparator_name(tmp.path()).unwrap(),
+        "leveldb.BytewiseComparator"
+    );
+}
+
+#[test]
+fn test_tuple_comparator() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+
+    let tmp = temp_dir("tuple_comparator");
+    // tenant ascending, then timestamp descending
+    let segments = [(1, SortOrder::Ascending), (4, SortOrder::Descending)];
+    let database =
+        Database::open_with_comparator(tmp.path(), &opts, TupleComparator::new(&segments)).unwrap();
+
+    for tenant in [2u8, 1] {
+        for timestamp in [10u32, 30, 20] {
+            let mut key = vec![tenant];
+            key.extend_from_slice(&timestamp.to_be_bytes());
+            db_put_u8_simple(&database, &key, &[tenant]);
+        }
+    }
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<(u8, u32)> = database
+        .keys_iter(&read_opts)
+        .map(|key| (key[0], u32::from_be_bytes([key[1], key[2], key[3], key[4]])))
+        .collect();
+    assert_eq!(
+        keys,
+        vec![(1, 30), (1, 20), (1, 10), (2, 30), (2, 20), (2, 10)]
+    );
+    drop(database);
+
+    let reordered = [(1, SortOrder::Ascending), (4, SortOrder::Ascending)];
+    let err = Database::open_with_comparator(tmp.path(), &opts, TupleComparator::new(&reordered))
+        .unwrap_err();
+    assert!(!err.is_already_open());
+}
@@ -1,7 +1,15 @@
 use leveldb::database::Database;
-use leveldb::options::Options;
+use leveldb::management::Checkpoint;
+use leveldb::options::{OpenOptions, Options, ReadOptions, WriteOptions};
 
-use crate::utils::temp_dir;
+use crate::utils::{db_put_simple, open_database, temp_dir};
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_open_database() {
@@ -24,3 +32,438 @@ fn test_open_nonexistent_database_without_create() {
     let res: Result<Database, _> = Database::open(tmp.path(), &opts);
     assert!(res.is_err());
 }
+
+#[test]
+fn test_default_verify_checksums() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.default_verify_checksums = true;
+    let tmp = temp_dir("verify_checksums");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &1).unwrap(), Some(vec![1]));
+    assert!(database.effective_read_options(&read_opts).verify_checksums);
+
+    let explicit = ReadOptions::new().with_verify_checksums(false);
+    assert_eq!(database.get(&explicit, &1).unwrap(), Some(vec![1]));
+    assert!(!database.effective_read_options(&explicit).verify_checksums);
+}
+
+#[test]
+fn test_open_create_parents() {
+    let tmp = temp_dir("create_parents");
+    let path = tmp.path().join("nested").join("dirs").join("db");
+
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    assert!(Database::open(&path, &opts).is_err());
+
+    opts.create_parents = true;
+    let database = Database::open(&path, &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+    assert_eq!(
+        database.get(&ReadOptions::new(), &1).unwrap(),
+        Some(vec![1])
+    );
+}
+
+#[test]
+fn test_open_auto_repair() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("auto_repair");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+    drop(database);
+    fs::write(tmp.path().join("CURRENT"), "garbage").unwrap();
+
+    opts.paranoid_checks = true;
+    let err = Database::open(tmp.path(), &opts).unwrap_err();
+    assert!(err.is_corruption());
+
+    opts.auto_repair = true;
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    assert!(database.was_repaired());
+    assert_eq!(
+        database.get(&ReadOptions::new(), &1).unwrap(),
+        Some(vec![1])
+    );
+
+    let database = database.reopen().unwrap();
+    assert!(!database.was_repaired());
+}
+
+#[test]
+fn test_reopen_database() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.error_if_exists = true;
+    let tmp = temp_dir("reopen");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+
+    let database = database.reopen().unwrap();
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &1).unwrap(), Some(vec![1]));
+}
+
+#[test]
+#[cfg(feature = "reuse-logs")]
+fn test_reopen_reuse_logs() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.reuse_logs = true;
+    let tmp = temp_dir("reuse_logs");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+
+    let database = database.reopen().unwrap();
+    db_put_simple(&database, &2, &[2]);
+    let database = database.reopen().unwrap();
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &1).unwrap(), Some(vec![1]));
+    assert_eq!(database.get(&read_opts, &2).unwrap(), Some(vec![2]));
+}
+
+#[test]
+fn test_close_database() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("close");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+    assert!(database.close().is_ok());
+
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &1).unwrap(), Some(vec![1]));
+}
+
+#[test]
+fn test_content_hash() {
+    let tmp1 = temp_dir("content_hash_1");
+    let tmp2 = temp_dir("content_hash_2");
+    let db1 = open_database(tmp1.path(), true);
+    let db2 = open_database(tmp2.path(), true);
+
+    for i in 0..10 {
+        db_put_simple(&db1, &i, &[i as u8]);
+    }
+    for i in (0..10).rev() {
+        db_put_simple(&db2, &i, &[i as u8]);
+    }
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(db1.content_hash(&read_opts), db2.content_hash(&read_opts));
+
+    db_put_simple(&db2, &3, &[42]);
+    assert_ne!(db1.content_hash(&read_opts), db2.content_hash(&read_opts));
+}
+
+#[test]
+#[cfg(feature = "max-file-size")]
+fn test_open_with_max_file_size() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.max_file_size = Some(4 * 1024 * 1024);
+    let tmp = temp_dir("max_file_size");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+
+    for i in 0..100 {
+        db_put_simple(&database, &i, &[i as u8; 100]);
+    }
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &42).unwrap(), Some(vec![42; 100]));
+}
+
+#[test]
+fn test_open_with_retry() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("open_with_retry");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+
+    let started = Instant::now();
+    let res = Database::open_with_retry(tmp.path(), &opts, 2, Duration::from_millis(10));
+    assert!(res.unwrap_err().is_lock_error());
+    assert!(started.elapsed() >= Duration::from_millis(20));
+
+    let closer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        drop(database);
+    });
+    let res = Database::open_with_retry(tmp.path(), &opts, 100, Duration::from_millis(10));
+    assert!(res.is_ok());
+    closer.join().unwrap();
+}
+
+#[test]
+fn test_open_with_retry_other_error() {
+    let opts = Options::new();
+    let tmp = temp_dir("open_with_retry_missing");
+
+    let started = Instant::now();
+    let res = Database::open_with_retry(tmp.path(), &opts, 5, Duration::from_secs(1));
+    assert!(!res.unwrap_err().is_lock_error());
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_open_with_timeout() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("open_with_timeout");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+
+    let mut open_opts = OpenOptions::new(opts);
+    let err = Database::open_with(tmp.path(), &open_opts).unwrap_err();
+    assert!(err.is_lock_error());
+
+    let closer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        drop(database);
+    });
+    open_opts.open_timeout = Some(Duration::from_secs(5));
+    assert!(Database::open_with(tmp.path(), &open_opts).is_ok());
+    closer.join().unwrap();
+}
+
+#[test]
+fn test_open_with_leftover_lock_file() {
+    let tmp = temp_dir("leftover_lock");
+    fs::write(tmp.path().join("LOCK"), b"").unwrap();
+
+    // the lock file of a crashed process is not locked anymore
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let database = Database::open_with(tmp.path(), &OpenOptions::new(opts)).unwrap();
+    db_put_simple(&database, &1, &[1]);
+    assert_eq!(
+        database.get(&ReadOptions::new(), &1).unwrap(),
+        Some(vec![1])
+    );
+}
+
+#[test]
+fn test_stats() {
+    let tmp = temp_dir("stats");
+    let database = open_database(tmp.path(), true);
+    for i in 0..100 {
+        db_put_simple(&database, &i, &[0; 100]);
+    }
+
+    let stats = database.stats();
+    assert_eq!(stats.num_files_per_level.len(), 7);
+    assert!(stats.approximate_memory_usage.unwrap() > 0);
+    assert!(stats.stats.contains("Compactions"));
+    assert!(database.property("leveldb.unknown").is_none());
+}
+
+#[test]
+fn test_open_error_if_exists() {
+    let tmp = temp_dir("error_if_exists");
+    drop(open_database(tmp.path(), true));
+
+    let mut opts = Options::new();
+    opts.error_if_exists = true;
+    let err = Database::open(tmp.path(), &opts).unwrap_err();
+    assert!(err.is_already_exists());
+    assert!(!err.is_lock_error());
+
+    let missing = temp_dir("error_if_exists_missing");
+    let err = Database::open(missing.path(), &Options::new()).unwrap_err();
+    assert!(!err.is_already_exists());
+}
+
+#[test]
+fn test_to_hashmap() {
+    let tmp = temp_dir("to_hashmap");
+    let database = open_database(tmp.path(), true);
+    db_put_simple(&database, &1, &[10]);
+    db_put_simple(&database, &2, &[20]);
+
+    let mut expected = HashMap::new();
+    expected.insert(1i32.to_be_bytes().to_vec(), vec![10]);
+    expected.insert(2i32.to_be_bytes().to_vec(), vec![20]);
+    assert_eq!(database.to_hashmap(&ReadOptions::new()), expected);
+}
+
+#[test]
+fn test_open_already_open_in_process() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("already_open");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+
+    let err = Database::open(tmp.path(), &opts).unwrap_err();
+    assert!(err.is_already_open());
+    let relative = tmp.path().join("..").join(tmp.path().file_name().unwrap());
+    assert!(Database::open(&relative, &opts)
+        .unwrap_err()
+        .is_already_open());
+
+    drop(database);
+    assert!(Database::open(tmp.path(), &opts).is_ok());
+}
+
+#[test]
+fn test_approximate_memory_usage() {
+    let tmp = temp_dir("approximate_memory_usage");
+    let database = open_database(tmp.path(), true);
+    let before = database.approximate_memory_usage().unwrap();
+    for i in 0..100 {
+        db_put_simple(&database, &i, &[0; 1000]);
+    }
+    assert!(database.approximate_memory_usage().unwrap() > before);
+}
+
+fn open_existing(path: &Path) -> Result<Database, Box<dyn std::error::Error>> {
+    Ok(Database::open(path, &Options::new())?)
+}
+
+#[test]
+fn test_error_into_boxed_error() {
+    let missing = temp_dir("boxed_error");
+    let err = open_existing(missing.path()).unwrap_err();
+    assert!(err.to_string().starts_with("LevelDB error: "));
+    assert!(err.to_string().contains("does not exist"));
+    assert!(err.source().is_none());
+
+    let tmp = temp_dir("boxed_error_io");
+    let database = open_database(&tmp.path().join("db"), true);
+    let file = tmp.path().join("file");
+    fs::write(&file, b"").unwrap();
+
+    let err = database.checkpoint(file.join("checkpoint")).unwrap_err();
+    let source = err.source().unwrap();
+    assert!(source.downcast_ref::<io::Error>().is_some());
+    assert!(err.to_string().starts_with("LevelDB error: IO error: "));
+}
+
+#[test]
+fn test_open_or_create() {
+    let tmp = temp_dir("open_or_create");
+    let path = tmp.path().join("db");
+
+    let (database, created) = Database::open_or_create(&path, &Options::new()).unwrap();
+    assert!(created);
+    db_put_simple(&database, &1, &[1]);
+    drop(database);
+
+    let (database, created) = Database::open_or_create(&path, &Options::new()).unwrap();
+    assert!(!created);
+    assert_eq!(
+        database.get(&ReadOptions::new(), &1).unwrap(),
+        Some(vec![1])
+    );
+}
+
+#[test]
+fn test_prefix_keys() {
+    let tmp = temp_dir("prefix_keys");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    for key in [
+        &b"user/"[..],
+        b"user/2",
+        b"user/10",
+        b"user0",
+        b"use",
+        b"order/1",
+    ] {
+        database.put_u8(&write_opts, key, b"value").unwrap();
+    }
+    database.put_u8(&write_opts, &[b'u', 0xff], b"").unwrap();
+    database.put_u8(&write_opts, &[b'u', 0xff, 1], b"").unwrap();
+    database.put_u8(&write_opts, b"v", b"").unwrap();
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(
+        database.prefix_keys(&read_opts, b"user/").unwrap(),
+        vec![b"user/".to_vec(), b"user/10".to_vec(), b"user/2".to_vec()]
+    );
+    assert_eq!(
+        database.prefix_keys(&read_opts, &[b'u', 0xff]).unwrap(),
+        vec![vec![b'u', 0xff], vec![b'u', 0xff, 1]]
+    );
+    assert!(database
+        .prefix_keys(&read_opts, b"missing")
+        .unwrap()
+        .is_empty());
+    assert_eq!(database.prefix_keys(&read_opts, b"").unwrap().len(), 9);
+}
+
+#[test]
+fn test_find_last() {
+    let tmp = temp_dir("find_last");
+    let database = open_database(tmp.path(), true);
+    for i in 1..=9u8 {
+        database
+            .put_u8(&WriteOptions::new(), &[i], &[i * 10 + i % 2])
+            .unwrap();
+    }
+
+    let read_opts = ReadOptions::new();
+    let even = |_: &[u8], value: &[u8]| value[0] & 1 == 0;
+    assert_eq!(
+        database.find_last(&read_opts, &[2], &[8], even).unwrap(),
+        Some((vec![6], vec![60]))
+    );
+    assert_eq!(
+        database.find_last(&read_opts, &[2], &[9], even).unwrap(),
+        Some((vec![8], vec![80]))
+    );
+    assert_eq!(
+        database.find_last(&read_opts, &[0], &[0xff], even).unwrap(),
+        Some((vec![8], vec![80]))
+    );
+    assert_eq!(
+        database.find_last(&read_opts, &[3], &[4], even).unwrap(),
+        None
+    );
+    assert_eq!(
+        database
+            .find_last(&read_opts, &[1], &[9], |key, _| key[0] < 3)
+            .unwrap(),
+        Some((vec![2], vec![20]))
+    );
+}
+
+#[test]
+fn test_info_log() {
+    let tmp = temp_dir("info_log");
+    let database = open_database(tmp.path(), true);
+    db_put_simple(&database, &1, &[1]);
+    drop(database);
+
+    let database = open_database(tmp.path(), false);
+    let log = database.info_log().unwrap();
+    assert!(log.iter().any(|line| line.contains("Recovering log")));
+}
+
+#[test]
+fn test_info_log_path() {
+    let tmp = temp_dir("info_log_path");
+    let logs = temp_dir("info_log_path_logs");
+    let log_path = logs.path().join("leveldb.log");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.info_log_path = Some(log_path.clone());
+
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    db_put_simple(&database, &1, &[1]);
+    let database = database.reopen().unwrap();
+    db_put_simple(&database, &2, &[2]);
+
+    assert!(!tmp.path().join("LOG").exists());
+    assert!(logs.path().join("leveldb.log.old").exists());
+    let log = database.info_log().unwrap();
+    assert!(log.iter().any(|line| line.contains("Recovering log")));
+    assert_eq!(
+        fs::read_to_string(&log_path).unwrap().lines().count(),
+        log.len()
+    );
+}
@@ -0,0 +1,82 @@
+use crate::utils::{db_put_u8_simple, open_database, temp_dir};
+use leveldb::compaction::Compaction;
+use leveldb::iterator::Iterable;
+use leveldb::options::{ReadOptions, WriteOptions};
+use std::ffi::OsStr;
+use std::fs;
+
+#[test]
+fn test_export_import() {
+    let tmp = temp_dir("export");
+    let database = open_database(tmp.path(), true);
+    db_put_u8_simple(&database, &[1], &[1]);
+    db_put_u8_simple(&database, &[2], &[]);
+    db_put_u8_simple(&database, &[3, 3], &[3, 3, 3]);
+
+    let read_opts = ReadOptions::new();
+    let mut dump = Vec::new();
+    let exported = database.export(&read_opts, &mut dump).unwrap();
+    assert_eq!(exported, 3);
+
+    let tmp2 = temp_dir("import");
+    let restored = open_database(tmp2.path(), true);
+    let write_opts = WriteOptions::new();
+    let imported = restored.import(&write_opts, &mut &dump[..]).unwrap();
+    assert_eq!(imported, 3);
+
+    let original: Vec<_> = database.iter(&read_opts).collect();
+    let copy: Vec<_> = restored.iter(&read_opts).collect();
+    assert_eq!(original, copy);
+}
+
+#[test]
+fn test_import_truncated() {
+    let tmp = temp_dir("import_truncated");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    let dump = [0, 0, 0, 1, 7, 0, 0];
+
+    assert!(database.import(&write_opts, &mut &dump[..]).is_err());
+    let read_opts = ReadOptions::new();
+    assert!(database.get_u8(&read_opts, &[7]).unwrap().is_none());
+}
+
+#[test]
+fn test_import_oversized_frame() {
+    let tmp = temp_dir("import_oversized_frame");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    // the length claims 4 GiB, but the input ends after two bytes
+    let dump = [0xff, 0xff, 0xff, 0xff, 7, 7];
+
+    assert!(database.import(&write_opts, &mut &dump[..]).is_err());
+}
+
+#[test]
+fn test_export_corruption() {
+    let tmp = temp_dir("export_corruption");
+    {
+        let database = open_database(tmp.path(), true);
+        for i in 0..100u8 {
+            db_put_u8_simple(&database, &[i], &[i; 100]);
+        }
+        database.compact(&[0], &[0xFF]);
+    }
+
+    let table = fs::read_dir(tmp.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension() == Some(OsStr::new("ldb")))
+        .unwrap();
+    let mut data = fs::read(&table).unwrap();
+    for byte in &mut data[50..60] {
+        *byte ^= 0xFF;
+    }
+    fs::write(&table, data).unwrap();
+
+    // the dump must not silently end at the corrupted block
+    let database = open_database(tmp.path(), false);
+    let read_opts = ReadOptions::new().with_verify_checksums(true);
+    let mut dump = Vec::new();
+    assert!(database.export(&read_opts, &mut dump).is_err());
+}
@@ -1,8 +1,16 @@
 use crate::utils::{db_put_simple, db_put_u8_simple, open_database, temp_dir};
+use leveldb::batch::{Batch, WriteBatch};
+use leveldb::compaction::Compaction;
+use leveldb::comparator::NumericPrefixComparator;
+use leveldb::database::Database;
 use leveldb::iterator::Iterable;
-use leveldb::iterator::LevelDBIterator;
-use leveldb::options::ReadOptions;
-use leveldb::util::FromU8;
+use leveldb::iterator::{merge_iter, LevelDBIterator, Precedence, ResumableScan};
+use leveldb::options::{Options, ReadOptions, WriteOptions};
+use leveldb::snapshots::Snapshots;
+use leveldb::util::{Endian, FromU8};
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::fs;
 
 #[test]
 fn test_iterator() {
@@ -67,6 +75,33 @@ fn test_iterator_last() {
     assert_eq!(iter.last().unwrap(), (vec![2], vec![2]));
 }
 
+#[test]
+fn test_iterator_last_bounded() {
+    let tmp = temp_dir("iter_last_bounded");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[2], &[2]);
+    db_put_u8_simple(database, &[4], &[4]);
+    db_put_u8_simple(database, &[5], &[5]);
+
+    let read_opts = ReadOptions::new();
+    let last = database.iter(&read_opts).from(&[3]).last();
+    assert_eq!(last, Some((vec![5], vec![5])));
+    assert_eq!(database.iter(&read_opts).from(&[6]).last(), None);
+
+    let last = database.iter(&read_opts).from(&[1]).to(&[3]).last();
+    assert_eq!(last, Some((vec![2], vec![2])));
+    assert_eq!(
+        database.iter(&read_opts).to(&[4]).last(),
+        Some((vec![4], vec![4]))
+    );
+    assert_eq!(database.iter(&read_opts).from(&[3]).to(&[3]).last(), None);
+    assert_eq!(
+        database.keys_iter(&read_opts).prefix(&[4]).last(),
+        Some(vec![4])
+    );
+}
+
 #[test]
 fn test_iterator_seek() {
     let tmp = temp_dir("from_seek");
@@ -120,6 +155,28 @@ fn test_iterator_from_reverse() {
     assert_eq!(iter.next(), None);
 }
 
+#[test]
+fn test_iterator_from_reverse_positioned() {
+    let tmp = temp_dir("iter_from_reverse_positioned");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[2], &[2]);
+    db_put_u8_simple(database, &[4], &[4]);
+    db_put_u8_simple(database, &[5], &[5]);
+
+    // reverse() already positions the iterator on the first key of its bounds
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts).from(&[3]).reverse();
+    assert!(iter.is_valid());
+    assert_eq!(iter.next().unwrap(), (vec![2], vec![2]));
+
+    let mut keys = database.keys_iter(&read_opts).from(&[4]).reverse();
+    assert_eq!(keys.next().unwrap(), vec![4]);
+
+    let mut values = database.value_iter(&read_opts).from(&[9]).reverse();
+    assert_eq!(values.next().unwrap(), vec![5]);
+}
+
 #[test]
 fn test_iterator_to_reverse() {
     let tmp = temp_dir("iter_to_reverse");
@@ -196,6 +253,107 @@ fn test_iterator_prefix() {
     assert_eq!(iter.next(), None);
 }
 
+#[test]
+fn test_iterator_prefix_reverse() {
+    let tmp = temp_dir("iter_prefix_reverse");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[2], &[2]);
+    db_put_u8_simple(database, &[2, 1], &[3]);
+    db_put_u8_simple(database, &[2, 0xFF], &[4]);
+    db_put_u8_simple(database, &[3, 1], &[5]);
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts).prefix(&[2]).reverse();
+    assert_eq!(iter.next().unwrap(), (vec![2, 0xFF], vec![4]));
+    assert_eq!(iter.next().unwrap(), (vec![2, 1], vec![3]));
+    assert_eq!(iter.next().unwrap(), (vec![2], vec![2]));
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn test_iterator_prefix_all_ff() {
+    let tmp = temp_dir("iter_prefix_ff");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[0xFF], &[2]);
+    db_put_u8_simple(database, &[0xFF, 0xFF], &[3]);
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.keys_iter(&read_opts).prefix(&[0xFF]).collect();
+    assert_eq!(keys, vec![vec![0xFF], vec![0xFF, 0xFF]]);
+
+    let keys: Vec<_> = database
+        .keys_iter(&read_opts)
+        .prefix(&[0xFF])
+        .reverse()
+        .collect();
+    assert_eq!(keys, vec![vec![0xFF, 0xFF], vec![0xFF]]);
+}
+
+// an iterator implemented outside the crate, relying on the default methods
+struct ForwardOnly<'a>(leveldb::iterator::Iterator<'a>);
+
+impl<'a> LevelDBIterator<'a> for ForwardOnly<'a> {
+    type RevIter = leveldb::iterator::RevIterator<'a>;
+
+    fn raw_iterator(&self) -> *mut leveldb_sys::leveldb_iterator_t {
+        self.0.raw_iterator()
+    }
+    fn start(&self) -> bool {
+        self.0.start()
+    }
+    fn started(&mut self) {
+        self.0.started()
+    }
+    fn reverse(self) -> Self::RevIter {
+        self.0.reverse()
+    }
+    fn from<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self {
+        ForwardOnly(self.0.from(key))
+    }
+    fn to<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self {
+        ForwardOnly(self.0.to(key))
+    }
+    fn prefix<K: AsRef<[u8]> + ?Sized>(self, key: &'a K) -> Self {
+        ForwardOnly(self.0.prefix(key))
+    }
+    fn from_key(&self) -> Option<&'a [u8]> {
+        self.0.from_key()
+    }
+    fn to_key(&self) -> Option<&'a [u8]> {
+        self.0.to_key()
+    }
+    fn prefix_key(&self) -> Option<&'a [u8]> {
+        self.0.prefix_key()
+    }
+    unsafe fn advance_raw(&mut self) {
+        self.0.advance_raw()
+    }
+}
+
+#[test]
+fn test_iterator_default_methods() {
+    let tmp = temp_dir("iter_default_methods");
+    let database = &mut open_database(tmp.path(), true);
+    for key in [&[1u8][..], &[2], &[2, 0xFF], &[3]] {
+        db_put_u8_simple(database, key, &[]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = ForwardOnly(database.iter(&read_opts)).prefix(&[2u8][..]);
+    assert!(!iter.is_reverse());
+    let mut keys = Vec::new();
+    while iter.advance(false) {
+        keys.push(iter.key());
+    }
+    assert_eq!(keys, vec![vec![2], vec![2, 0xFF]]);
+
+    let iter = ForwardOnly(database.iter(&read_opts)).prefix(&[2u8][..]);
+    iter.seek_to_last();
+    assert_eq!(iter.key(), vec![2, 0xFF]);
+}
+
 #[test]
 fn test_key_iterator() {
     let tmp = temp_dir("iter_key");
@@ -222,3 +380,827 @@ fn test_value_iterator() {
     let value = iter.next().unwrap();
     assert_eq!(value, vec![1]);
 }
+
+#[test]
+fn test_keys_decoded() {
+    let tmp = temp_dir("iter_keys_decoded");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_simple(database, &1, &[1]);
+    db_put_simple(database, &2, &[2]);
+    db_put_simple(database, &3, &[3]);
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<i32> = database.keys_iter(&read_opts).keys_decoded().collect();
+    assert_eq!(keys, vec![1, 2, 3]);
+
+    let entries: Vec<(i32, Vec<u8>)> = database
+        .iter(&read_opts)
+        .reverse()
+        .entries_decoded()
+        .collect();
+    assert_eq!(entries, vec![(3, vec![3]), (2, vec![2]), (1, vec![1])]);
+}
+
+#[test]
+#[should_panic(expected = "cannot decode i32 from 1 bytes")]
+fn test_keys_decoded_malformed() {
+    let tmp = temp_dir("iter_keys_decoded_malformed");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+
+    let read_opts = ReadOptions::new();
+    let _: Vec<i32> = database.keys_iter(&read_opts).keys_decoded().collect();
+}
+
+#[test]
+fn test_iterator_sample() {
+    let tmp = temp_dir("iter_sample");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..10u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.keys_iter(&read_opts).sample(3).collect();
+    assert_eq!(keys, vec![vec![0], vec![3], vec![6], vec![9]]);
+
+    let entries: Vec<_> = database.iter(&read_opts).to(&[7]).sample(3).collect();
+    assert_eq!(
+        entries,
+        vec![(vec![0], vec![0]), (vec![3], vec![3]), (vec![6], vec![6])]
+    );
+
+    let keys: Vec<_> = database.keys_iter(&read_opts).reverse().sample(4).collect();
+    assert_eq!(keys, vec![vec![9], vec![5], vec![1]]);
+
+    assert_eq!(database.keys_iter(&read_opts).sample(0).count(), 10);
+}
+
+#[test]
+fn test_iterator_is_valid() {
+    let tmp = temp_dir("iter_is_valid");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 1..6u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts).from(&[2]).to(&[4]);
+    let mut keys = Vec::new();
+    iter.advance(false);
+    while iter.is_valid() {
+        keys.push(iter.key());
+        iter.advance(false);
+    }
+    assert_eq!(keys, vec![vec![2], vec![3], vec![4]]);
+
+    iter.seek(&[3]);
+    assert!(iter.is_valid());
+    assert_eq!(iter.key(), vec![3]);
+    iter.seek(&[5]);
+    assert!(!iter.is_valid());
+
+    let mut iter = database.iter(&read_opts).reverse();
+    iter.advance(true);
+    assert!(iter.is_valid());
+    assert_eq!(iter.key(), vec![5]);
+}
+
+#[test]
+fn test_iterator_try_next() {
+    let tmp = temp_dir("iter_try_next");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[2], &[2]);
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert_eq!(iter.try_next().unwrap(), Some((vec![1], vec![1])));
+    assert_eq!(iter.try_next().unwrap(), Some((vec![2], vec![2])));
+    assert_eq!(iter.try_next().unwrap(), None);
+    assert!(iter.last_error().is_none());
+}
+
+#[test]
+fn test_iterator_try_next_corruption() {
+    let tmp = temp_dir("iter_try_next_corruption");
+    {
+        let database = open_database(tmp.path(), true);
+        for i in 0..100u8 {
+            db_put_u8_simple(&database, &[i], &[i; 100]);
+        }
+        database.compact(&[0], &[0xFF]);
+    }
+
+    let table = fs::read_dir(tmp.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension() == Some(OsStr::new("ldb")))
+        .unwrap();
+    let mut data = fs::read(&table).unwrap();
+    for byte in &mut data[50..60] {
+        *byte ^= 0xFF;
+    }
+    fs::write(&table, data).unwrap();
+
+    let database = open_database(tmp.path(), false);
+    let mut read_opts = ReadOptions::new();
+    read_opts.verify_checksums = true;
+    let mut iter = database.iter(&read_opts);
+    let result = loop {
+        match iter.try_next() {
+            Ok(Some(_)) => continue,
+            other => break other,
+        }
+    };
+    assert!(result.is_err());
+    assert!(iter.last_error().is_some());
+}
+
+#[test]
+fn test_iterator_bounds_as_ref() {
+    let tmp = temp_dir("iter_bounds_as_ref");
+    let database = &mut open_database(tmp.path(), true);
+    for key in ["key1", "key2", "key3", "key4", "other"] {
+        db_put_simple(database, &key, key.as_bytes());
+    }
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.keys_iter(&read_opts).from("key3").collect();
+    assert_eq!(
+        keys,
+        vec![b"key3".to_vec(), b"key4".to_vec(), b"other".to_vec()]
+    );
+
+    let upper = String::from("key2");
+    let keys: Vec<_> = database.keys_iter(&read_opts).to(&upper).collect();
+    assert_eq!(keys, vec![b"key1".to_vec(), b"key2".to_vec()]);
+
+    let prefix = b"key".to_vec();
+    assert_eq!(database.keys_iter(&read_opts).prefix(&prefix).count(), 4);
+}
+
+#[test]
+fn test_prefix_scan() {
+    let tmp = temp_dir("prefix_scan");
+    let database = &mut open_database(tmp.path(), true);
+    let batch = WriteBatch::new();
+    for prefix in 0..10u8 {
+        for i in 0..=255u8 {
+            batch.put_u8(&[prefix, i], &[i]);
+        }
+    }
+    database.write(&WriteOptions::new(), &batch).unwrap();
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database
+        .prefix_scan(&read_opts, &[5])
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(keys.len(), 256);
+    assert_eq!(keys.first(), Some(&vec![5, 0]));
+    assert_eq!(keys.last(), Some(&vec![5, 255]));
+
+    let snapshot = database.snapshot();
+    assert_eq!(snapshot.prefix_scan(&read_opts, &[9, 255]).count(), 1);
+    assert_eq!(snapshot.prefix_scan(&read_opts, &[10]).count(), 0);
+}
+
+#[test]
+fn test_iterator_status() {
+    let tmp = temp_dir("iter_status");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[1]);
+    db_put_u8_simple(database, &[2], &[2]);
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert!(iter.status().is_ok());
+    assert_eq!(iter.by_ref().count(), 2);
+    assert!(iter.status().is_ok());
+}
+
+#[test]
+fn test_iterator_into_btreemap() {
+    let tmp = temp_dir("into_btreemap");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 1..=5u8 {
+        db_put_u8_simple(database, &[i], &[i * 10]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let map = database
+        .iter(&read_opts)
+        .from(&[2])
+        .to(&[4])
+        .into_btreemap();
+    let entries: Vec<_> = map.into_iter().collect();
+    assert_eq!(
+        entries,
+        vec![
+            (vec![2], vec![20]),
+            (vec![3], vec![30]),
+            (vec![4], vec![40])
+        ]
+    );
+}
+
+#[test]
+fn test_scan_range_pages() {
+    let tmp = temp_dir("scan_range");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..10u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut start = vec![0u8];
+    let mut pages = Vec::new();
+    loop {
+        let page = database.scan_range(&read_opts, &start, &[10], 3).unwrap();
+        if page.is_empty() {
+            break;
+        }
+        start = page.last().unwrap().0.clone();
+        start.push(0);
+        pages.push(page.iter().map(|(k, _)| k[0]).collect::<Vec<_>>());
+    }
+    assert_eq!(
+        pages,
+        vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], vec![9]]
+    );
+
+    let bounded = database.scan_range(&read_opts, &[2], &[4], 10).unwrap();
+    assert_eq!(bounded, vec![(vec![2], vec![2]), (vec![3], vec![3])]);
+    assert!(database
+        .scan_range(&read_opts, &[0], &[10], 0)
+        .unwrap()
+        .is_empty());
+    assert!(database.scan_range(&read_opts, &[5], &[1], 3).is_err());
+}
+
+#[test]
+fn test_iterator_map_keys() {
+    let tmp = temp_dir("map_keys");
+    let database = &mut open_database(tmp.path(), true);
+    for ts in [1_000u64, 2_000, 3_000].iter() {
+        db_put_u8_simple(database, &ts.to_be_bytes(), b"event");
+    }
+
+    let read_opts = ReadOptions::new();
+    let entries: Vec<(u64, Vec<u8>)> = database.iter(&read_opts).map_keys(u64::from_u8).collect();
+    assert_eq!(
+        entries,
+        vec![
+            (1_000, b"event".to_vec()),
+            (2_000, b"event".to_vec()),
+            (3_000, b"event".to_vec())
+        ]
+    );
+}
+
+#[test]
+fn test_iterator_pairs() {
+    let tmp = temp_dir("pairs");
+    let database = &mut open_database(tmp.path(), true);
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.iter(&read_opts).pairs().count(), 0);
+
+    db_put_u8_simple(database, &[1], &[10]);
+    assert_eq!(database.iter(&read_opts).pairs().count(), 0);
+
+    db_put_u8_simple(database, &[3], &[30]);
+    db_put_u8_simple(database, &[4], &[40]);
+    db_put_u8_simple(database, &[8], &[80]);
+    let gaps: Vec<u8> = database
+        .iter(&read_opts)
+        .pairs()
+        .map(|((k1, _), (k2, _))| k2[0] - k1[0])
+        .collect();
+    assert_eq!(gaps, vec![2, 1, 4]);
+
+    let (first, second) = database.iter(&read_opts).pairs().next().unwrap();
+    assert_eq!(first, (vec![1], vec![10]));
+    assert_eq!(second, (vec![3], vec![30]));
+}
+
+#[test]
+fn test_iterator_take_while_prefix() {
+    let tmp = temp_dir("take_while_prefix");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1, 0], &[10]);
+    db_put_u8_simple(database, &[2, 0], &[20]);
+    db_put_u8_simple(database, &[2, 1], &[21]);
+    db_put_u8_simple(database, &[3, 0], &[30]);
+    db_put_u8_simple(database, &[3, 1], &[31]);
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts).from(&[2]);
+    let twos: Vec<_> = iter.take_while_prefix(&[2]).map(|(k, _)| k).collect();
+    assert_eq!(twos, vec![vec![2, 0], vec![2, 1]]);
+    let threes: Vec<_> = iter.take_while_prefix(&[3]).map(|(k, _)| k).collect();
+    assert_eq!(threes, vec![vec![3, 0], vec![3, 1]]);
+    assert_eq!(iter.next(), None);
+
+    let mut iter = database.iter(&read_opts);
+    assert_eq!(iter.take_while_prefix(&[9]).count(), 0);
+    assert_eq!(iter.next(), Some((vec![1, 0], vec![10])));
+}
+
+#[test]
+fn test_warm_range() {
+    let tmp = temp_dir("warm_range");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..20u8 {
+        db_put_u8_simple(database, &[i], &[i; 64]);
+    }
+    database.compact(&[0], &[20]);
+
+    let mut read_opts = ReadOptions::new();
+    read_opts.fill_cache = false;
+    assert_eq!(database.warm_range(&read_opts, &[5], &[15]).unwrap(), 10);
+    assert_eq!(database.warm_range(&read_opts, &[18], &[50]).unwrap(), 2);
+    assert_eq!(database.warm_range(&read_opts, &[30], &[50]).unwrap(), 0);
+
+    let entries = database.scan_range(&read_opts, &[5], &[15], 100).unwrap();
+    assert_eq!(entries.len(), 10);
+}
+
+#[test]
+fn test_iterator_strip_prefix() {
+    let tmp = temp_dir("strip_prefix");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, b"groups/admins", &[0]);
+    db_put_u8_simple(database, b"users/alice", &[1]);
+    db_put_u8_simple(database, b"users/bob", &[2]);
+    db_put_u8_simple(database, b"userz", &[3]);
+
+    let read_opts = ReadOptions::new();
+    let names: Vec<Vec<u8>> = database
+        .keys_iter(&read_opts)
+        .strip_prefix(b"users/")
+        .collect();
+    assert_eq!(names, vec![b"alice".to_vec(), b"bob".to_vec()]);
+
+    let entries: Vec<_> = database.iter(&read_opts).strip_prefix(b"users/").collect();
+    assert_eq!(
+        entries,
+        vec![(b"alice".to_vec(), vec![1]), (b"bob".to_vec(), vec![2])]
+    );
+}
+
+#[test]
+fn test_iterator_nth() {
+    let tmp = temp_dir("iter_nth");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 1..=6u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert_eq!(iter.nth(3), Some((vec![4], vec![4])));
+    assert_eq!(iter.next(), Some((vec![5], vec![5])));
+    assert_eq!(iter.nth(5), None);
+
+    let mut keys = database.keys_iter(&read_opts).from(&[2]).to(&[4]);
+    assert_eq!(keys.nth(1), Some(vec![3]));
+    assert_eq!(keys.nth(1), None);
+
+    let mut rev = database.keys_iter(&read_opts).reverse();
+    assert_eq!(rev.nth(1), Some(vec![5]));
+    assert_eq!(rev.nth(2), Some(vec![2]));
+
+    // skipping ends on the same entry as collecting all items
+    let bounded = || database.iter(&read_opts).from(&[2]).to(&[5]);
+    let reversed = || database.value_iter(&read_opts).reverse().to(&[2]);
+    let all_bounded: Vec<_> = bounded().collect();
+    let all_reversed: Vec<_> = reversed().collect();
+    for n in 0..8 {
+        assert_eq!(bounded().nth(n), all_bounded.get(n).cloned());
+        assert_eq!(reversed().nth(n), all_reversed.get(n).cloned());
+    }
+}
+
+#[test]
+fn test_iterator_filter_keys() {
+    let tmp = temp_dir("filter_keys");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..100u8 {
+        db_put_u8_simple(database, &[i], &[i, i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let kept: Vec<_> = database
+        .iter(&read_opts)
+        .filter_keys(|key| key[0] % 20 == 0)
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            (vec![0], vec![0, 0]),
+            (vec![20], vec![20, 20]),
+            (vec![40], vec![40, 40]),
+            (vec![60], vec![60, 60]),
+            (vec![80], vec![80, 80])
+        ]
+    );
+}
+
+#[test]
+fn test_iterator_tell_seek() {
+    let tmp = temp_dir("tell_seek");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 1..=6u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert_eq!(iter.tell(), None);
+    iter.next();
+    iter.next();
+    let saved = iter.tell().unwrap();
+    assert_eq!(saved, vec![2]);
+
+    iter.seek(&[5]);
+    assert_eq!(iter.tell(), Some(vec![5]));
+
+    iter.seek(&saved);
+    assert_eq!(iter.next(), Some((vec![3], vec![3])));
+    assert_eq!(iter.next(), Some((vec![4], vec![4])));
+
+    iter.by_ref().for_each(drop);
+    assert_eq!(iter.tell(), None);
+}
+
+#[test]
+fn test_iterator_tell_bounded() {
+    let tmp = temp_dir("tell_bounded");
+    let database = &mut open_database(tmp.path(), true);
+    for key in [&[1u8][..], &[2, 1], &[2, 2], &[3], &[4]] {
+        db_put_u8_simple(database, key, b"v");
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts).to(&[3u8][..]);
+    iter.by_ref().for_each(drop);
+    assert_eq!(iter.tell(), None);
+
+    let mut iter = database.iter(&read_opts).prefix(&[2u8][..]);
+    assert_eq!(iter.next().unwrap().0, vec![2, 1]);
+    assert_eq!(iter.tell(), Some(vec![2, 1]));
+    iter.by_ref().for_each(drop);
+    assert_eq!(iter.tell(), None);
+
+    let mut iter = database.iter(&read_opts).reverse().to(&[2u8, 2][..]);
+    assert_eq!(iter.next().unwrap().0, vec![4]);
+    assert_eq!(iter.tell(), Some(vec![4]));
+    iter.by_ref().for_each(drop);
+    assert_eq!(iter.tell(), None);
+}
+
+#[test]
+fn test_iterator_chunks() {
+    let tmp = temp_dir("chunks");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..10u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let chunks: Vec<_> = database.iter(&read_opts).chunks(4).collect();
+    assert_eq!(
+        chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(),
+        vec![4, 4, 2]
+    );
+    assert_eq!(chunks[2], vec![(vec![8], vec![8]), (vec![9], vec![9])]);
+    assert_eq!(
+        chunks
+            .into_iter()
+            .flatten()
+            .map(|(k, _)| k[0])
+            .collect::<Vec<_>>(),
+        (0..10).collect::<Vec<_>>()
+    );
+}
+
+fn sum_values<I: Iterator<Item = (Vec<u8>, Vec<u8>)>>(entries: I) -> u32 {
+    entries.map(|(_, value)| value[0] as u32).sum()
+}
+
+#[test]
+fn test_iterator_remaining() {
+    let tmp = temp_dir("remaining");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..5u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert!(iter.advance(false));
+    assert_eq!(iter.key(), vec![0]);
+    assert!(iter.advance(false));
+    assert_eq!(iter.key(), vec![1]);
+
+    let rest: Vec<_> = iter.remaining().collect();
+    assert_eq!(
+        rest,
+        vec![(vec![2], vec![2]), (vec![3], vec![3]), (vec![4], vec![4])]
+    );
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_iterator_by_ref() {
+    let tmp = temp_dir("by_ref");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..5u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let mut iter = database.iter(&read_opts);
+    assert_eq!(iter.next(), Some((vec![0], vec![0])));
+    assert_eq!(sum_values(iter.by_ref().take(2)), 3);
+    assert_eq!(iter.key(), vec![2]);
+    assert_eq!(sum_values(iter.remaining()), 7);
+
+    let mut keys = database.keys_iter(&read_opts);
+    assert!(keys.advance(false));
+    assert_eq!(keys.remaining().count(), 4);
+}
+
+#[test]
+#[should_panic(expected = "chunk size must not be zero")]
+fn test_iterator_chunks_zero() {
+    let tmp = temp_dir("chunks_zero");
+    let database = open_database(tmp.path(), true);
+
+    database.iter(&ReadOptions::new()).chunks(0);
+}
+
+#[test]
+fn test_reverse_iterator_latest_per_prefix() {
+    let tmp = temp_dir("latest_per_prefix");
+    let database = &mut open_database(tmp.path(), true);
+    // keys are a 1-byte id followed by a 1-byte version
+    for (id, versions) in [(1u8, 3u8), (2, 1), (4, 2)] {
+        for version in 1..=versions {
+            db_put_u8_simple(database, &[id, version], &[id * 10 + version]);
+        }
+    }
+
+    let read_opts = ReadOptions::new();
+    let latest: Vec<_> = database
+        .iter(&read_opts)
+        .reverse()
+        .latest_per_prefix(1)
+        .collect();
+    assert_eq!(
+        latest,
+        vec![
+            (vec![4, 2], vec![42]),
+            (vec![2, 1], vec![21]),
+            (vec![1, 3], vec![13])
+        ]
+    );
+}
+
+#[test]
+fn test_iterator_count() {
+    let tmp = temp_dir("count");
+    let database = &mut open_database(tmp.path(), true);
+    let value = vec![0u8; 1024];
+    let batch = WriteBatch::new();
+    for prefix in [b"aaa", b"bbb", b"ccc"] {
+        for i in 0..1_000u16 {
+            let mut key = prefix.to_vec();
+            key.extend_from_slice(&i.to_be_bytes());
+            batch.put_u8(&key, &value);
+        }
+    }
+    database.write(&WriteOptions::new(), &batch).unwrap();
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.prefix_scan(&read_opts, b"bbb").count(), 1_000);
+    assert_eq!(
+        database.prefix_scan(&read_opts, b"bbb").reverse().count(),
+        1_000
+    );
+    assert_eq!(database.iter(&read_opts).count(), 3_000);
+    assert_eq!(
+        database
+            .keys_iter(&read_opts)
+            .from(b"aaa\x03\xe0")
+            .to(b"bbb\x00\x09")
+            .count(),
+        18
+    );
+    assert_eq!(
+        database.value_iter(&read_opts).prefix(b"ccc").count(),
+        1_000
+    );
+    assert_eq!(database.iter(&read_opts).prefix(b"ddd").count(), 0);
+
+    // entries already returned are not counted again
+    let mut iter = database.prefix_scan(&read_opts, b"ccc");
+    iter.next();
+    assert_eq!(iter.count(), 999);
+}
+
+#[test]
+fn test_try_iter() {
+    let tmp = temp_dir("try_iter");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 1..=5u8 {
+        db_put_u8_simple(database, &[i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let entries = database
+        .try_iter(&read_opts)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[4], (vec![5], vec![5]));
+
+    let mut results = database.iter(&read_opts).from(&[2]).to(&[3]).results();
+    assert_eq!(results.next().unwrap().unwrap(), (vec![2], vec![2]));
+    assert_eq!(results.next().unwrap().unwrap(), (vec![3], vec![3]));
+    assert!(results.next().is_none());
+    assert!(results.next().is_none());
+}
+
+#[test]
+fn test_iterator_values_contain() {
+    let tmp = temp_dir("values_contain");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, b"a", b"red apple");
+    db_put_u8_simple(database, b"b", b"banana");
+    db_put_u8_simple(database, b"c", b"apple pie");
+    db_put_u8_simple(database, b"d", b"app");
+    db_put_u8_simple(database, b"e", b"");
+
+    let read_opts = ReadOptions::new();
+    let keys: Vec<_> = database.iter(&read_opts).values_contain(b"apple").collect();
+    assert_eq!(keys, vec![b"a".to_vec(), b"c".to_vec()]);
+
+    let keys: Vec<_> = database
+        .iter(&read_opts)
+        .from(b"b")
+        .values_contain(b"an")
+        .collect();
+    assert_eq!(keys, vec![b"b".to_vec()]);
+    assert_eq!(database.iter(&read_opts).values_contain(b"").count(), 5);
+}
+
+#[test]
+fn test_iterator_inspect_entry() {
+    let tmp = temp_dir("inspect_entry");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..6u8 {
+        db_put_u8_simple(database, &[i], &[i * 2]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let inspected = Cell::new(0);
+    let entries: Vec<_> = database
+        .iter(&read_opts)
+        .from(&[1])
+        .inspect_entry(|key, value| {
+            assert_eq!(value, &[key[0] * 2]);
+            inspected.set(inspected.get() + 1);
+        })
+        .collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(inspected.get(), entries.len());
+    assert_eq!(entries[0], (vec![1], vec![2]));
+}
+
+#[test]
+fn test_resumable_scan() {
+    let tmp = temp_dir("resumable_scan");
+    let mut scan = ResumableScan::new();
+    let mut seen = Vec::new();
+    {
+        let database = open_database(tmp.path(), true);
+        for i in 0..10u8 {
+            db_put_u8_simple(&database, &[i], &[i]);
+        }
+        seen.extend(scan.resume(&database, &ReadOptions::new()).take(5));
+        assert_eq!(scan.last_key(), Some(&[4u8][..]));
+    }
+
+    let database = open_database(tmp.path(), false);
+    // the resume key itself was deleted meanwhile
+    database.delete_u8(&WriteOptions::new(), &[4]).unwrap();
+    seen.extend(scan.resume(&database, &ReadOptions::new()));
+
+    let keys: Vec<u8> = seen.iter().map(|(k, _)| k[0]).collect();
+    assert_eq!(keys, (0..10).collect::<Vec<_>>());
+    assert_eq!(scan.last_key(), Some(&[9u8][..]));
+    assert_eq!(scan.resume(&database, &ReadOptions::new()).count(), 0);
+}
+
+#[test]
+fn test_merge_iter() {
+    let tmp_a = temp_dir("merge_iter_a");
+    let tmp_b = temp_dir("merge_iter_b");
+    let db_a = open_database(tmp_a.path(), true);
+    let db_b = open_database(tmp_b.path(), true);
+    for key in [&b"a"[..], b"c", b"d", b"f"] {
+        db_put_u8_simple(&db_a, key, b"old");
+    }
+    for key in [&b"b"[..], b"c", b"f", b"g"] {
+        db_put_u8_simple(&db_b, key, b"new");
+    }
+
+    let read_opts = ReadOptions::new();
+    let merged: Vec<_> = merge_iter(&db_a, &db_b, &read_opts)
+        .unwrap()
+        .precedence(Precedence::Second)
+        .collect();
+    let expected: Vec<(&[u8], &[u8])> = vec![
+        (b"a", b"old"),
+        (b"b", b"new"),
+        (b"c", b"new"),
+        (b"d", b"old"),
+        (b"f", b"new"),
+        (b"g", b"new"),
+    ];
+    assert_eq!(
+        merged,
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_vec(), v.to_vec()))
+            .collect::<Vec<_>>()
+    );
+
+    let merged: Vec<_> = merge_iter(&db_a, &db_b, &read_opts).unwrap().collect();
+    assert_eq!(merged.len(), 6);
+    assert!(merged.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(merged[2], (b"c".to_vec(), b"old".to_vec()));
+    assert_eq!(merged[4], (b"f".to_vec(), b"old".to_vec()));
+}
+
+#[test]
+fn test_merge_iter_empty() {
+    let tmp_a = temp_dir("merge_iter_empty_a");
+    let tmp_b = temp_dir("merge_iter_empty_b");
+    let db_a = open_database(tmp_a.path(), true);
+    let db_b = open_database(tmp_b.path(), true);
+    db_put_u8_simple(&db_b, b"only", b"b");
+
+    let read_opts = ReadOptions::new();
+    let merged: Vec<_> = merge_iter(&db_a, &db_b, &read_opts).unwrap().collect();
+    assert_eq!(merged, vec![(b"only".to_vec(), b"b".to_vec())]);
+    assert_eq!(merge_iter(&db_a, &db_a, &read_opts).unwrap().count(), 0);
+}
+
+#[test]
+fn test_merge_iter_custom_comparator() {
+    let tmp_a = temp_dir("merge_iter_comparator_a");
+    let tmp_b = temp_dir("merge_iter_comparator_b");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let comparator = || NumericPrefixComparator::new(4, Endian::Little);
+    let db_a = Database::open_with_comparator(tmp_a.path(), &opts, comparator()).unwrap();
+    let db_b = Database::open_with_comparator(tmp_b.path(), &opts, comparator()).unwrap();
+    for n in [1u32, 256, 3] {
+        db_put_u8_simple(&db_a, &n.to_le_bytes(), b"a");
+    }
+    for n in [2u32, 256, 512] {
+        db_put_u8_simple(&db_b, &n.to_le_bytes(), b"b");
+    }
+
+    let merged: Vec<(u32, Vec<u8>)> = merge_iter(&db_a, &db_b, &ReadOptions::new())
+        .unwrap()
+        .map(|(k, v)| (u32::from_le_bytes([k[0], k[1], k[2], k[3]]), v))
+        .collect();
+    assert_eq!(
+        merged,
+        vec![
+            (1, b"a".to_vec()),
+            (2, b"b".to_vec()),
+            (3, b"a".to_vec()),
+            (256, b"a".to_vec()),
+            (512, b"b".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_iter_comparator_mismatch() {
+    let tmp_a = temp_dir("merge_iter_mismatch_a");
+    let tmp_b = temp_dir("merge_iter_mismatch_b");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let comparator = NumericPrefixComparator::new(4, Endian::Big);
+    let db_a = Database::open_with_comparator(tmp_a.path(), &opts, comparator).unwrap();
+    let db_b = open_database(tmp_b.path(), true);
+
+    let err = merge_iter(&db_a, &db_b, &ReadOptions::new()).err().unwrap();
+    assert!(err.to_string().contains("different comparators"));
+}
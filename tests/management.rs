@@ -1,6 +1,13 @@
-use crate::utils::{open_database, temp_dir};
+use crate::utils::{db_put_u8_simple, open_database, temp_dir};
+use leveldb::compaction::Compaction;
+use leveldb::database::Database;
+use leveldb::iterator::Iterable;
 use leveldb::management::*;
 use leveldb::options::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 fn test_destroy_database() {
@@ -25,3 +32,213 @@ fn test_repair_database() {
     let res = repair(tmp.path(), &options);
     assert!(res.is_ok());
 }
+
+#[test]
+fn test_namespaces() {
+    let tmp = temp_dir("namespaces");
+    let database = open_database(tmp.path(), true);
+    db_put_u8_simple(&database, b"other", b"0");
+
+    let users = database.namespace(b"users/");
+    let groups = database.namespace(b"groups/");
+    let write_opts = WriteOptions::new();
+    users.put(&write_opts, &"alice", b"1").unwrap();
+    users.put(&write_opts, &"bob", b"2").unwrap();
+    groups.put(&write_opts, &"admins", b"3").unwrap();
+    groups.put(&write_opts, &"bob", b"4").unwrap();
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(users.get(&read_opts, &"bob").unwrap(), Some(b"2".to_vec()));
+    assert_eq!(groups.get(&read_opts, &"bob").unwrap(), Some(b"4".to_vec()));
+    assert_eq!(users.get(&read_opts, &"admins").unwrap(), None);
+    assert_eq!(
+        database.get_u8(&read_opts, b"users/alice").unwrap(),
+        Some(b"1".to_vec())
+    );
+
+    let entries: Vec<_> = users.iter(&read_opts).collect();
+    assert_eq!(
+        entries,
+        vec![
+            (b"alice".to_vec(), b"1".to_vec()),
+            (b"bob".to_vec(), b"2".to_vec())
+        ]
+    );
+    let keys: Vec<_> = groups.keys_iter(&read_opts).collect();
+    assert_eq!(keys, vec![b"admins".to_vec(), b"bob".to_vec()]);
+
+    users.delete(&write_opts, &"alice").unwrap();
+    let values: Vec<_> = users.value_iter(&read_opts).collect();
+    assert_eq!(values, vec![b"2".to_vec()]);
+}
+
+#[test]
+fn test_checkpoint() {
+    let tmp = temp_dir("checkpoint");
+    let dest = temp_dir("checkpoint_dest");
+    let database = open_database(tmp.path(), true);
+    for i in 0..100u8 {
+        db_put_u8_simple(&database, &[i], &[0; 100]);
+    }
+    database.compact(&[0], &[100]);
+    db_put_u8_simple(&database, &[200], &[1]);
+
+    database.checkpoint(dest.path().join("backup")).unwrap();
+    db_put_u8_simple(&database, &[201], &[2]);
+
+    let backup = open_database(&dest.path().join("backup"), false);
+    let read_opts = ReadOptions::new();
+    assert_eq!(
+        backup.get_u8(&read_opts, &[50]).unwrap(),
+        Some(vec![0; 100])
+    );
+    assert_eq!(backup.get_u8(&read_opts, &[200]).unwrap(), Some(vec![1]));
+    assert_eq!(backup.get_u8(&read_opts, &[201]).unwrap(), None);
+
+    db_put_u8_simple(&backup, &[202], &[3]);
+    assert_eq!(database.get_u8(&read_opts, &[202]).unwrap(), None);
+}
+
+#[test]
+fn test_checkpoint_concurrent_writer() {
+    let tmp = temp_dir("checkpoint_concurrent");
+    let dest = temp_dir("checkpoint_concurrent_dest");
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.write_buffer_size = Some(16 * 1024);
+    let database = Arc::new(Database::open(tmp.path(), &opts).unwrap());
+
+    // keys are written in order, so every checkpoint holds a prefix of them
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let database = Arc::clone(&database);
+        let done = Arc::clone(&done);
+        thread::spawn(move || {
+            for i in 0..20_000u32 {
+                db_put_u8_simple(&database, &i.to_be_bytes(), &[0; 64]);
+            }
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+    let mut checkpoints = 0;
+    while !done.load(Ordering::SeqCst) {
+        let backup_path = dest.path().join(checkpoints.to_string());
+        database.checkpoint(&backup_path).unwrap();
+        checkpoints += 1;
+
+        let backup = open_database(&backup_path, false);
+        let keys: Vec<Vec<u8>> = backup.keys_iter(&ReadOptions::new()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(key, &(i as u32).to_be_bytes());
+        }
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn test_metadata() {
+    let tmp = temp_dir("metadata");
+    let database = open_database(tmp.path(), true);
+    database.set_meta("schema_version", &[3]).unwrap();
+    db_put_u8_simple(&database, b"schema_version", &[42]);
+    assert_eq!(database.get_meta("missing").unwrap(), None);
+
+    let database = database.reopen().unwrap();
+    assert_eq!(database.get_meta("schema_version").unwrap(), Some(vec![3]));
+    assert_eq!(
+        database
+            .get_u8(&ReadOptions::new(), b"schema_version")
+            .unwrap(),
+        Some(vec![42])
+    );
+}
+
+#[test]
+fn test_list_files() {
+    let tmp = temp_dir("list_files");
+    let database = open_database(tmp.path(), true);
+    for i in 0..10u8 {
+        db_put_u8_simple(&database, &[i], &[i]);
+    }
+    database.compact(&[0], &[9]);
+
+    let files = list_files(tmp.path()).unwrap();
+    let count = |file_type| files.iter().filter(|f| f.file_type == file_type).count();
+    assert_eq!(count(FileType::Current), 1);
+    assert_eq!(count(FileType::Lock), 1);
+    assert!(count(FileType::Manifest) >= 1);
+    assert!(count(FileType::Log) >= 1);
+    assert!(count(FileType::Sstable) >= 1);
+    assert!(files
+        .iter()
+        .any(|f| f.name == "LOG" && f.file_type == FileType::Other));
+
+    let current = files.iter().find(|f| f.name == "CURRENT").unwrap();
+    assert!(current.size > 0);
+    drop(database);
+
+    // works on a closed database as well
+    assert_eq!(list_files(tmp.path()).unwrap().len(), files.len());
+    assert!(list_files(tmp.path().join("missing")).is_err());
+}
+
+#[test]
+fn test_ttl_database() {
+    let tmp = temp_dir("ttl_database");
+    let database = open_database(tmp.path(), true);
+    let ttl_db = TtlDatabase::new(&database);
+    let read_opts = ReadOptions::new();
+    let write_opts = WriteOptions::new();
+
+    ttl_db
+        .put_ttl(&write_opts, b"live", b"value", Duration::from_secs(3600))
+        .unwrap();
+    ttl_db
+        .put_ttl(&write_opts, b"empty", b"", Duration::from_secs(3600))
+        .unwrap();
+    ttl_db
+        .put_ttl(&write_opts, b"expired", b"value", Duration::from_millis(0))
+        .unwrap();
+    assert_eq!(
+        ttl_db.get(&read_opts, b"live").unwrap(),
+        Some(b"value".to_vec())
+    );
+    assert_eq!(ttl_db.get(&read_opts, b"empty").unwrap(), Some(vec![]));
+    assert_eq!(ttl_db.get(&read_opts, b"missing").unwrap(), None);
+    // the expiry takes 8 bytes
+    assert_eq!(
+        database.get_u8(&read_opts, b"live").unwrap().unwrap().len(),
+        13
+    );
+
+    assert_eq!(ttl_db.get(&read_opts, b"expired").unwrap(), None);
+    // the expired entry was deleted when read
+    assert_eq!(database.get_u8(&read_opts, b"expired").unwrap(), None);
+    assert_eq!(ttl_db.purge_expired(&write_opts).unwrap(), 0);
+    assert_eq!(ttl_db.get(&read_opts, b"live").unwrap().unwrap(), b"value");
+}
+
+#[test]
+fn test_ttl_database_purge_expired() {
+    let tmp = temp_dir("ttl_database_purge");
+    let database = open_database(tmp.path(), true);
+    let ttl_db = TtlDatabase::new(&database);
+    let write_opts = WriteOptions::new();
+
+    for i in 0..10u8 {
+        let ttl = if i < 6 {
+            Duration::from_millis(0)
+        } else {
+            Duration::from_secs(3600)
+        };
+        ttl_db.put_ttl(&write_opts, &[i], &[i], ttl).unwrap();
+    }
+    db_put_u8_simple(&database, b"raw", b"x");
+
+    assert_eq!(ttl_db.purge_expired(&write_opts).unwrap(), 6);
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.keys_iter(&read_opts).count(), 5);
+    assert_eq!(ttl_db.get(&read_opts, &[7]).unwrap(), Some(vec![7]));
+    assert!(ttl_db.get(&read_opts, b"raw").unwrap_err().is_corruption());
+    assert_eq!(ttl_db.purge_expired(&write_opts).unwrap(), 0);
+}
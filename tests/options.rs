@@ -0,0 +1,102 @@
+use crate::utils::temp_dir;
+use leveldb::compaction::Compaction;
+use leveldb::database::Database;
+use leveldb::options::{Options, ReadOptions, WriteOptions};
+use leveldb_sys::Compression;
+
+#[test]
+fn test_read_options_point_read() {
+    let opts = ReadOptions::point_read();
+    assert!(opts.fill_cache);
+    assert!(!opts.verify_checksums);
+    assert!(opts.use_database_default);
+}
+
+#[test]
+fn test_read_options_scan() {
+    let opts = ReadOptions::scan();
+    assert!(!opts.fill_cache);
+    assert!(!opts.verify_checksums);
+    assert!(!opts.use_database_default);
+}
+
+#[test]
+fn test_read_options_new_unchanged() {
+    let opts = ReadOptions::new();
+    assert!(opts.fill_cache);
+    assert!(!opts.verify_checksums);
+    assert!(opts.use_database_default);
+
+    let opts = ReadOptions::new().with_verify_checksums(true);
+    assert!(opts.verify_checksums);
+    assert!(!opts.use_database_default);
+}
+
+#[test]
+fn test_write_options_presets() {
+    assert!(!WriteOptions::new().sync);
+    assert!(!WriteOptions::fast().sync);
+    assert!(WriteOptions::durable().sync);
+    assert!(WriteOptions::fast().with_sync(true).sync);
+    assert!(!WriteOptions::durable().with_sync(false).sync);
+}
+
+#[test]
+fn test_options_validate() {
+    let mut opts = Options::new();
+    assert!(opts.validate().is_ok());
+
+    opts.max_open_files = Some(64);
+    assert!(opts.validate().is_ok());
+
+    if cfg!(unix) {
+        opts.max_open_files = Some(i32::MAX);
+        let err = opts.validate().unwrap_err();
+        assert!(err.to_string().contains("max_open_files"));
+    }
+}
+
+#[test]
+fn test_options_bulk_load_profile() {
+    let opts = Options::bulk_load_profile();
+    assert_eq!(opts.write_buffer_size, Some(64 * 1024 * 1024));
+    #[cfg(feature = "max-file-size")]
+    assert_eq!(opts.max_file_size, Some(64 * 1024 * 1024));
+    assert!(matches!(opts.compression, Compression::No));
+    assert!(opts.filter_policy.is_none());
+    assert!(!opts.create_if_missing);
+}
+
+#[test]
+fn test_options_point_lookup_profile() {
+    let opts = Options::point_lookup_profile(16);
+    assert_eq!(opts.block_size, Some(4 * 1024));
+    assert_eq!(opts.block_restart_interval, Some(4));
+    assert!(opts.cache.is_some());
+    assert_eq!(opts.filter_policy.as_ref().unwrap().bits_per_key(), 10);
+    assert!(opts.write_buffer_size.is_none());
+    assert!(!opts.create_if_missing);
+}
+
+#[test]
+fn test_options_profiles_open() {
+    for (name, mut opts) in [
+        ("bulk_load_profile", Options::bulk_load_profile()),
+        ("point_lookup_profile", Options::point_lookup_profile(1)),
+    ] {
+        let tmp = temp_dir(name);
+        opts.create_if_missing = true;
+        let database = Database::open(tmp.path(), &opts).unwrap();
+        database
+            .put_u8(&WriteOptions::new(), b"key", b"value")
+            .unwrap();
+        database.compact(b"", b"\xff");
+
+        let read_opts = ReadOptions::new();
+        assert_eq!(
+            database.get_u8(&read_opts, b"key").unwrap(),
+            Some(b"value".to_vec())
+        );
+        assert_eq!(database.get_u8(&read_opts, b"missing").unwrap(), None);
+    }
+}
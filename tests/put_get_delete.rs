@@ -1,5 +1,12 @@
-use crate::utils::{db_put_simple, open_database, temp_dir};
+use crate::utils::{db_put_simple, db_put_u8_simple, open_database, temp_dir};
+use leveldb::database::bytes::BufferPool;
+use leveldb::iterator::Iterable;
+use leveldb::key::{KeyTag, TypedKey};
 use leveldb::options::{ReadOptions, WriteOptions};
+use leveldb::util::FromU8;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
 
 #[test]
 fn test_write_to_database() {
@@ -39,6 +46,27 @@ fn test_get_from_empty_database() {
     }
 }
 
+#[test]
+fn test_smart_pointer_keys() {
+    let tmp = temp_dir("smart_pointer_keys");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    let read_opts = ReadOptions::new();
+
+    let key: Rc<[u8]> = Rc::from(&b"rc"[..]);
+    let value = Arc::new(vec![1u8, 2]);
+    database.put(&write_opts, &key, &value).unwrap();
+    assert_eq!(database.get(&read_opts, &key).unwrap(), Some(vec![1, 2]));
+
+    let key = Arc::new(b"rc".to_vec());
+    assert_eq!(database.get(&read_opts, &key).unwrap(), Some(vec![1, 2]));
+
+    database
+        .delete(&write_opts, &Box::<[u8]>::from(&b"rc"[..]))
+        .unwrap();
+    assert!(database.get(&read_opts, &key).unwrap().is_none());
+}
+
 #[test]
 fn test_get_from_filled_database() {
     let tmp = temp_dir("get_filled");
@@ -59,3 +87,321 @@ fn test_get_from_filled_database() {
         }
     }
 }
+
+#[test]
+fn test_value_len() {
+    let tmp = temp_dir("value_len");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_u8_simple(database, &[1], &[]);
+    db_put_u8_simple(database, &[2], &[1, 2, 3]);
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.value_len(&read_opts, &[0]).unwrap(), None);
+    assert_eq!(database.value_len(&read_opts, &[1]).unwrap(), Some(0));
+    assert_eq!(database.value_len(&read_opts, &[2]).unwrap(), Some(3));
+}
+
+#[test]
+fn test_delete_many() {
+    let tmp = temp_dir("delete_many");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..10 {
+        db_put_simple(database, &i, &[i as u8]);
+    }
+
+    let write_opts = WriteOptions::new();
+    database
+        .delete_many(&write_opts, (0..10).filter(|i| i % 2 == 0))
+        .unwrap();
+
+    let read_opts = ReadOptions::new();
+    for i in 0..10 {
+        let value = database.get(&read_opts, &i).unwrap();
+        if i % 2 == 0 {
+            assert!(value.is_none());
+        } else {
+            assert_eq!(value, Some(vec![i as u8]));
+        }
+    }
+}
+
+#[test]
+fn test_with_read_options() {
+    let tmp = temp_dir("with_read_options");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_simple(database, &1, &[1]);
+
+    let res = database.with_read_options(|opts| database.get(opts, &1));
+    assert_eq!(res.unwrap(), Some(vec![1]));
+    assert!(!database.default_read_options().verify_checksums);
+}
+
+#[test]
+fn test_modify() {
+    let tmp = temp_dir("modify");
+    let database = &mut open_database(tmp.path(), true);
+    let read_opts = ReadOptions::new();
+    let write_opts = WriteOptions::new();
+
+    for _ in 0..5 {
+        database
+            .modify(&read_opts, &write_opts, b"counter", |value| {
+                let count = value.map_or(0, |v| u64::from_u8(&v));
+                Some((count + 1).to_be_bytes().to_vec())
+            })
+            .unwrap();
+    }
+    let value = database.get_u8(&read_opts, b"counter").unwrap().unwrap();
+    assert_eq!(u64::from_u8(&value), 5);
+
+    database
+        .modify(&read_opts, &write_opts, b"counter", |_| None)
+        .unwrap();
+    assert!(database.get_u8(&read_opts, b"counter").unwrap().is_none());
+}
+
+#[test]
+fn test_put_get_str() {
+    let tmp = temp_dir("put_get_str");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    let read_opts = ReadOptions::new();
+
+    database
+        .put_str(&write_opts, b"json", "{\"name\": \"h\u{e9}llo\"}")
+        .unwrap();
+    assert_eq!(
+        database.get_str(&read_opts, b"json").unwrap(),
+        Some("{\"name\": \"h\u{e9}llo\"}".to_string())
+    );
+    assert_eq!(database.get_str(&read_opts, b"missing").unwrap(), None);
+
+    database
+        .put_u8(&write_opts, b"binary", &[0xff, 0xfe])
+        .unwrap();
+    assert!(database.get_str(&read_opts, b"binary").is_err());
+}
+
+#[test]
+fn test_get_bundle() {
+    let tmp = temp_dir("get_bundle");
+    let database = open_database(tmp.path(), true);
+    db_put_u8_simple(&database, b"a", b"alpha");
+    db_put_u8_simple(&database, b"c", b"gamma");
+
+    let read_opts = ReadOptions::new();
+    let keys: [&[u8]; 3] = [b"a", b"b", b"c"];
+    let bundle = database.get_bundle(&read_opts, &keys).unwrap();
+    assert_eq!(bundle.len(), 3);
+    assert_eq!(bundle.get(0), Some(&b"alpha"[..]));
+    assert_eq!(bundle.get(1), None);
+    assert_eq!(bundle.get(2), Some(&b"gamma"[..]));
+    assert_eq!(bundle.get(3), None);
+}
+
+#[test]
+fn test_put_returning() {
+    let tmp = temp_dir("put_returning");
+    let database = open_database(tmp.path(), true);
+    let read_opts = ReadOptions::new();
+    let write_opts = WriteOptions::new();
+
+    let previous = database
+        .put_returning(&read_opts, &write_opts, b"key", b"first")
+        .unwrap();
+    assert_eq!(previous, None);
+    let previous = database
+        .put_returning(&read_opts, &write_opts, b"key", b"second")
+        .unwrap();
+    assert_eq!(previous, Some(b"first".to_vec()));
+    assert_eq!(
+        database.get_u8(&read_opts, b"key").unwrap(),
+        Some(b"second".to_vec())
+    );
+}
+
+#[test]
+fn test_delete_existing() {
+    let tmp = temp_dir("delete_existing");
+    let database = open_database(tmp.path(), true);
+    db_put_u8_simple(&database, b"present", b"");
+    let read_opts = ReadOptions::new();
+    let write_opts = WriteOptions::new();
+
+    assert!(database
+        .delete_existing(&read_opts, &write_opts, b"present")
+        .unwrap());
+    assert_eq!(database.get_u8(&read_opts, b"present").unwrap(), None);
+    assert!(!database
+        .delete_existing(&read_opts, &write_opts, b"present")
+        .unwrap());
+    assert!(!database
+        .delete_existing(&read_opts, &write_opts, b"absent")
+        .unwrap());
+}
+
+#[test]
+fn test_get_default() {
+    let tmp = temp_dir("get_default");
+    let database = Arc::new(open_database(tmp.path(), true));
+    for i in 0..1_000u32 {
+        db_put_u8_simple(&database, &i.to_be_bytes(), &(i * 3).to_be_bytes());
+    }
+
+    let readers: Vec<_> = (0..4u32)
+        .map(|t| {
+            let database = Arc::clone(&database);
+            thread::spawn(move || {
+                for round in 0..10u32 {
+                    for i in 0..1_000u32 {
+                        let key = (i + t + round).to_be_bytes();
+                        let expected = database.get_u8(&ReadOptions::new(), &key).unwrap();
+                        assert_eq!(database.get_default(&key).unwrap(), expected);
+                    }
+                }
+            })
+        })
+        .collect();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+
+    assert_eq!(
+        database.get_default(&7u32.to_be_bytes()).unwrap(),
+        Some(21u32.to_be_bytes().to_vec())
+    );
+    assert_eq!(database.get_default(b"missing").unwrap(), None);
+}
+
+#[test]
+fn test_get_pooled() {
+    let tmp = temp_dir("get_pooled");
+    let database = open_database(tmp.path(), true);
+    for i in 0..10u8 {
+        db_put_u8_simple(&database, &[i], &vec![i; i as usize * 10]);
+    }
+
+    let pool = BufferPool::new(2);
+    let read_opts = ReadOptions::new();
+    for _ in 0..5 {
+        for i in 0..10u8 {
+            let value = database
+                .get_pooled(&read_opts, &[i], &pool)
+                .unwrap()
+                .unwrap();
+            assert_eq!(&*value, &vec![i; i as usize * 10][..]);
+        }
+    }
+    assert_eq!(pool.idle(), 1);
+
+    let first = database.get_pooled(&read_opts, &[1], &pool).unwrap();
+    let second = database.get_pooled(&read_opts, &[2], &pool).unwrap();
+    let third = database.get_pooled(&read_opts, &[3], &pool).unwrap();
+    assert!(database
+        .get_pooled(&read_opts, &[20], &pool)
+        .unwrap()
+        .is_none());
+    drop((first, second, third));
+    assert_eq!(pool.idle(), 2);
+}
+
+struct UserId;
+
+impl KeyTag for UserId {
+    const TAG: &'static [u8] = b"user";
+}
+
+struct UserIdx;
+
+impl KeyTag for UserIdx {
+    const TAG: &'static [u8] = b"use";
+}
+
+#[test]
+fn test_typed_keys() {
+    let tmp = temp_dir("typed_keys");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    let read_opts = ReadOptions::new();
+
+    // "use" + "r1" and "user" + "1" would collide without the tag length
+    let user = TypedKey::<UserId>::new(b"1");
+    let index = TypedKey::<UserIdx>::new(b"r1");
+    assert_eq!(user.key(), b"1");
+    assert_ne!(user.as_bytes(), index.as_bytes());
+
+    database.put_keyed(&write_opts, &user, b"alice").unwrap();
+    database.put_keyed(&write_opts, &index, b"index").unwrap();
+
+    assert_eq!(
+        database.get_keyed(&read_opts, &user).unwrap(),
+        Some(b"alice".to_vec())
+    );
+    assert_eq!(
+        database.get_keyed(&read_opts, &index).unwrap(),
+        Some(b"index".to_vec())
+    );
+    assert_eq!(
+        database
+            .get_keyed(&read_opts, &TypedKey::<UserIdx>::new(b"1"))
+            .unwrap(),
+        None
+    );
+    assert_eq!(database.get_u8(&read_opts, b"1").unwrap(), None);
+    assert_eq!(
+        database.get(&read_opts, &user).unwrap(),
+        Some(b"alice".to_vec())
+    );
+}
+
+#[test]
+fn test_get_sorted() {
+    let tmp = temp_dir("get_sorted");
+    let database = open_database(tmp.path(), true);
+    for i in 0..100u8 {
+        db_put_u8_simple(&database, &[1, i], &[i]);
+    }
+
+    let read_opts = ReadOptions::new();
+    let keys: [&[u8]; 6] = [&[1, 42], &[1, 7], &[1, 200], &[1, 43], &[0], &[1, 7]];
+    let values = database.get_sorted(&read_opts, &keys).unwrap();
+    assert_eq!(
+        values,
+        vec![
+            Some(vec![42]),
+            Some(vec![7]),
+            None,
+            Some(vec![43]),
+            None,
+            Some(vec![7])
+        ]
+    );
+    assert!(database.get_sorted(&read_opts, &[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_empty_key_and_value() {
+    let tmp = temp_dir("empty_key_value");
+    let database = open_database(tmp.path(), true);
+    let write_opts = WriteOptions::new();
+    let read_opts = ReadOptions::new();
+
+    database.put_u8(&write_opts, b"", b"empty key").unwrap();
+    database.put_u8(&write_opts, b"k", b"").unwrap();
+
+    assert_eq!(
+        database.get_u8(&read_opts, b"").unwrap(),
+        Some(b"empty key".to_vec())
+    );
+    assert_eq!(database.get_u8(&read_opts, b"k").unwrap(), Some(vec![]));
+    assert_eq!(database.value_len(&read_opts, b"k").unwrap(), Some(0));
+
+    let entries: Vec<_> = database.iter(&read_opts).collect();
+    assert_eq!(
+        entries,
+        vec![(vec![], b"empty key".to_vec()), (b"k".to_vec(), vec![])]
+    );
+
+    database.delete_u8(&write_opts, b"").unwrap();
+    assert_eq!(database.get_u8(&read_opts, b"").unwrap(), None);
+}
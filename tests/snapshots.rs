@@ -1,8 +1,11 @@
-use crate::utils::{db_put_simple, open_database, temp_dir};
-use leveldb::iterator::Iterable;
+use crate::utils::{db_put_simple, db_put_u8_simple, open_database, temp_dir};
+use leveldb::compaction::Compaction;
+use leveldb::database::Database;
+use leveldb::iterator::{Iterable, LevelDBIterator};
 use leveldb::options::ReadOptions;
-use leveldb::snapshots::Snapshots;
+use leveldb::snapshots::{OwnedSnapshotIter, SharedSnapshots, Snapshots};
 use leveldb::util::FromU8;
+use std::sync::Arc;
 
 #[test]
 fn test_snapshots() {
@@ -44,3 +47,141 @@ fn test_snapshot_iterator() {
     let next = iter.next();
     assert_eq!(None, next);
 }
+
+#[test]
+fn test_snapshot_drop() {
+    let tmp = temp_dir("snap_drop");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_simple(database, &1, &[1]);
+
+    {
+        let snapshot = database.snapshot();
+        db_put_simple(database, &2, &[2]);
+        assert_eq!(snapshot.keys_iter(&ReadOptions::new()).count(), 1);
+    }
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(database.get(&read_opts, &2).unwrap(), Some(vec![2]));
+    assert_eq!(database.keys_iter(&read_opts).count(), 2);
+
+    let snapshot = database.snapshot();
+    drop(snapshot);
+    assert_eq!(database.get(&read_opts, &1).unwrap(), Some(vec![1]));
+}
+
+#[test]
+fn test_scan_consistent() {
+    let tmp = temp_dir("scan_consistent");
+    let database = &mut open_database(tmp.path(), true);
+    db_put_simple(database, &1, &[1]);
+    db_put_simple(database, &2, &[2]);
+
+    let read_opts = ReadOptions::new();
+    let mut scan = database.scan_consistent(&read_opts);
+    assert_eq!(scan.next(), Some((1i32.to_be_bytes().to_vec(), vec![1])));
+
+    db_put_simple(database, &0, &[0]);
+    db_put_simple(database, &3, &[3]);
+    assert_eq!(scan.snapshot().get(&read_opts, &3).unwrap(), None);
+
+    assert_eq!(scan.next(), Some((2i32.to_be_bytes().to_vec(), vec![2])));
+    assert_eq!(scan.next(), None);
+    drop(scan);
+
+    assert_eq!(database.keys_iter(&read_opts).count(), 4);
+}
+
+#[test]
+fn test_par_ranges() {
+    let tmp = temp_dir("par_ranges");
+    let database = &mut open_database(tmp.path(), true);
+    for i in 0..3000u32 {
+        db_put_u8_simple(database, &i.to_be_bytes(), &[0; 100]);
+    }
+
+    for compacted in [false, true].iter() {
+        if *compacted {
+            database.compact(&[0], &[0xff]);
+        }
+        let snapshot = database.snapshot();
+        let ranges = database.par_ranges(&snapshot, 3);
+        assert_eq!(ranges.len(), 3);
+
+        let read_opts = ReadOptions::new();
+        let mut total = 0;
+        for (i, (start, end)) in ranges.iter().enumerate() {
+            assert!(start < end);
+            if i > 0 {
+                assert_eq!(&ranges[i - 1].1, start);
+            }
+            let count = snapshot
+                .keys_iter(&read_opts)
+                .from(start)
+                .take_while(|k| k < end)
+                .count();
+            assert!(count > 500, "unbalanced range of {} keys", count);
+            total += count;
+        }
+        assert_eq!(total, 3000);
+        assert!(ranges[0].0 <= 0u32.to_be_bytes().to_vec());
+    }
+}
+
+#[test]
+fn test_outstanding_snapshots() {
+    let tmp = temp_dir("outstanding_snapshots");
+    let database = open_database(tmp.path(), true);
+    assert!(database.outstanding_snapshots().is_empty());
+
+    let first = database.snapshot();
+    let second = database.snapshot();
+    let outstanding = database.outstanding_snapshots();
+    assert_eq!(outstanding.len(), 2);
+    assert!(outstanding[0].created <= outstanding[1].created);
+    assert!(outstanding[0].age >= outstanding[1].age);
+
+    drop(first);
+    let outstanding = database.outstanding_snapshots();
+    assert_eq!(outstanding.len(), 1);
+    assert_eq!(outstanding[0].id, 1);
+
+    drop(second);
+    let scan = database.scan_consistent(&ReadOptions::new());
+    assert_eq!(database.outstanding_snapshots().len(), 1);
+    drop(scan);
+    assert!(database.outstanding_snapshots().is_empty());
+}
+
+fn snapshot_entries(database: &Arc<Database>) -> OwnedSnapshotIter {
+    database.owned_snapshot_iter(&ReadOptions::new())
+}
+
+#[test]
+fn test_owned_snapshot_iter() {
+    let tmp = temp_dir("owned_snapshot_iter");
+    let database = Arc::new(open_database(tmp.path(), true));
+    db_put_u8_simple(&database, &[1], &[1]);
+    db_put_u8_simple(&database, &[2], &[2]);
+
+    let mut entries = snapshot_entries(&database);
+    db_put_u8_simple(&database, &[3], &[3]);
+    assert_eq!(database.outstanding_snapshots().len(), 1);
+
+    assert_eq!(entries.next(), Some((vec![1], vec![1])));
+    drop(database);
+    // the iterator keeps the database open
+    assert_eq!(entries.collect::<Vec<_>>(), vec![(vec![2], vec![2])]);
+}
+
+#[test]
+fn test_owned_snapshot_iter_release() {
+    let tmp = temp_dir("owned_snapshot_iter_release");
+    let database = Arc::new(open_database(tmp.path(), true));
+    db_put_u8_simple(&database, &[1], &[1]);
+
+    let entries = snapshot_entries(&database);
+    assert_eq!(database.outstanding_snapshots().len(), 1);
+    drop(entries);
+    assert!(database.outstanding_snapshots().is_empty());
+    assert_eq!(Arc::strong_count(&database), 1);
+}
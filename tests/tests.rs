@@ -1,11 +1,16 @@
+mod bytes;
 mod cache;
 mod compaction;
 mod comparator;
 mod concurrent_access;
 mod database;
+mod export;
 mod iterator;
 mod management;
+mod options;
 mod put_get_delete;
 mod snapshots;
+mod transaction;
+mod util;
 mod utils;
 mod writebatch;
@@ -0,0 +1,86 @@
+use crate::utils::{db_put_u8_simple, open_database, temp_dir};
+use leveldb::options::{ReadOptions, WriteOptions};
+use leveldb::transaction::Transaction;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn test_transaction_commit() {
+    let tmp = temp_dir("transaction_commit");
+    let database = open_database(tmp.path(), true);
+    db_put_u8_simple(&database, b"balance", &[10]);
+    db_put_u8_simple(&database, b"old", &[1]);
+
+    let read_opts = ReadOptions::new();
+    let transaction = Transaction::new(&database);
+    let balance = transaction.get_u8(&read_opts, b"balance").unwrap().unwrap();
+    transaction.put_u8(b"balance", &[balance[0] - 3]);
+    transaction.delete_u8(b"old");
+    // writes are visible to the transaction, but not to the database yet
+    assert_eq!(
+        transaction.get_u8(&read_opts, b"balance").unwrap(),
+        Some(vec![7])
+    );
+    assert_eq!(transaction.get_u8(&read_opts, b"old").unwrap(), None);
+    assert_eq!(
+        database.get_u8(&read_opts, b"balance").unwrap(),
+        Some(vec![10])
+    );
+
+    // writes to keys the transaction did not read don't conflict
+    db_put_u8_simple(&database, b"other", &[2]);
+    transaction.commit(&WriteOptions::new()).unwrap();
+
+    assert_eq!(
+        database.get_u8(&read_opts, b"balance").unwrap(),
+        Some(vec![7])
+    );
+    assert_eq!(database.get_u8(&read_opts, b"old").unwrap(), None);
+    assert_eq!(
+        database.get_u8(&read_opts, b"other").unwrap(),
+        Some(vec![2])
+    );
+}
+
+#[test]
+fn test_transaction_conflict() {
+    let tmp = temp_dir("transaction_conflict");
+    let database = Arc::new(open_database(tmp.path(), true));
+    db_put_u8_simple(&database, b"balance", &[10]);
+
+    let read_opts = ReadOptions::new();
+    let transaction = Transaction::new(&database);
+    let balance = transaction.get_u8(&read_opts, b"balance").unwrap().unwrap();
+    transaction.put_u8(b"balance", &[balance[0] - 3]);
+    transaction.put_u8(b"log", b"withdrew 3");
+
+    let writer = Arc::clone(&database);
+    thread::spawn(move || db_put_u8_simple(&writer, b"balance", &[20]))
+        .join()
+        .unwrap();
+
+    let err = transaction.commit(&WriteOptions::new()).unwrap_err();
+    assert!(err.is_conflict());
+    assert_eq!(
+        database.get_u8(&read_opts, b"balance").unwrap(),
+        Some(vec![20])
+    );
+    assert_eq!(database.get_u8(&read_opts, b"log").unwrap(), None);
+}
+
+#[test]
+fn test_transaction_conflict_on_missing_key() {
+    let tmp = temp_dir("transaction_conflict_missing");
+    let database = open_database(tmp.path(), true);
+
+    let read_opts = ReadOptions::new();
+    let transaction = Transaction::new(&database);
+    assert_eq!(transaction.get(&read_opts, &"lock").unwrap(), None);
+    transaction.put(&"lock", b"mine");
+
+    db_put_u8_simple(&database, b"lock", b"theirs");
+    assert!(transaction
+        .commit(&WriteOptions::new())
+        .unwrap_err()
+        .is_conflict());
+}
@@ -0,0 +1,71 @@
+use leveldb::util::{from_hex, to_hex, ConversionError, EndianU8, FromU8, TryFromU8};
+
+#[test]
+fn test_try_from_u8() {
+    assert_eq!(i32::try_from_u8(&[0, 0, 1, 2]), Ok(258));
+    assert_eq!(u8::try_from_u8(&[7]), Ok(7));
+}
+
+#[test]
+fn test_try_from_u8_too_short() {
+    let err = i32::try_from_u8(&[0, 1, 2]).unwrap_err();
+    assert_eq!(
+        err,
+        ConversionError {
+            target: "i32",
+            expected: 4,
+            actual: 3
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "cannot decode i32 from 3 bytes, expected 4"
+    );
+}
+
+#[test]
+fn test_try_from_u8_too_long() {
+    assert!(u16::try_from_u8(&[0, 1, 2]).is_err());
+    assert!(u64::try_from_u8(&[0; 9]).is_err());
+}
+
+#[test]
+fn test_endian_round_trip() {
+    let value: i32 = -123_456;
+    assert_eq!(i32::from_u8_be(&value.to_u8_be()), value);
+    assert_eq!(i32::from_u8_le(&value.to_u8_le()), value);
+    assert_eq!(value.to_u8_le(), value.to_le_bytes().to_vec());
+    assert_eq!(i32::from_u8(&value.to_u8_be()), value);
+
+    let value: u64 = 0x0102_0304_0506_0708;
+    assert_eq!(u64::from_u8_be(&value.to_u8_be()), value);
+    assert_eq!(u64::from_u8_le(&value.to_u8_le()), value);
+    assert_eq!(u64::from_u8_le(&[8, 7, 6, 5, 4, 3, 2, 1]), value);
+    assert_eq!(u64::from_u8_be(&[1, 2, 3, 4, 5, 6, 7, 8]), value);
+}
+
+#[test]
+fn test_hex_round_trip() {
+    assert_eq!(to_hex(&[0, 1, 0xab, 0xff]), "0001abff");
+    assert_eq!(to_hex(b""), "");
+    for bytes in [&b""[..], b"user/42", &[0, 0x7f, 0x80, 0xff]] {
+        assert_eq!(from_hex(&to_hex(bytes)).unwrap(), bytes);
+    }
+}
+
+#[test]
+fn test_from_hex_case() {
+    assert_eq!(from_hex("ABcd0f").unwrap(), vec![0xab, 0xcd, 0x0f]);
+    assert_eq!(from_hex("ABCD").unwrap(), from_hex("abcd").unwrap());
+}
+
+#[test]
+fn test_from_hex_invalid() {
+    let err = from_hex("xyz").unwrap_err();
+    assert!(err.to_string().contains("odd number of digits"));
+    let err = from_hex("xy").unwrap_err();
+    assert!(err.to_string().contains("'x' is not a hex digit"));
+    assert!(from_hex("abc").is_err());
+    assert!(from_hex("0g").is_err());
+    assert!(from_hex("é0").is_err());
+}
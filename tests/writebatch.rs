@@ -1,6 +1,7 @@
 use crate::utils::temp_dir;
 use leveldb::database::batch::{Batch, WriteBatch, WriteBatchIterator};
 use leveldb::database::Database;
+use leveldb::iterator::Iterable;
 use leveldb::options::{Options, ReadOptions, WriteOptions};
 
 #[test]
@@ -82,3 +83,182 @@ fn test_write_batch_iter() {
     assert_eq!(iter2.put, 2);
     assert_eq!(iter2.deleted, 1);
 }
+
+#[test]
+fn test_bulk_load() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    opts.write_buffer_size = Some(16 * 1024 * 1024);
+    let tmp = temp_dir("bulk_load");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+
+    let entries = (0..1000u32).map(|i| (i.to_be_bytes(), i.to_le_bytes()));
+    let wopts = WriteOptions::new();
+    assert_eq!(database.bulk_load(&wopts, entries).unwrap(), 1000);
+
+    let read_opts = ReadOptions::new();
+    for i in 0..1000u32 {
+        let value = database.get(&read_opts, &i).unwrap();
+        assert_eq!(value, Some(i.to_le_bytes().to_vec()));
+    }
+}
+
+#[test]
+fn test_write_batch_approximate_size() {
+    let batch = WriteBatch::new();
+    let empty = batch.approximate_size();
+
+    batch.put_u8(b"key", b"value");
+    let one = batch.approximate_size();
+    assert!(one >= empty + 8);
+    assert!(one <= empty + 8 + 3);
+
+    batch.put_u8(b"key2", &[0; 1000]);
+    let two = batch.approximate_size();
+    assert!(two >= one + 1004);
+    assert!(two <= one + 1004 + 4);
+
+    batch.delete_u8(b"key");
+    assert!(batch.approximate_size() > two);
+
+    batch.clear();
+    assert_eq!(batch.approximate_size(), empty);
+}
+
+#[test]
+fn test_write_batch_delete_range() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("writebatch_delete_range");
+    let database = &Database::open(tmp.path(), &opts).unwrap();
+    let write_opts = WriteOptions::new();
+    for i in 1..=5u8 {
+        database.put_u8(&write_opts, &[i], &[i]).unwrap();
+    }
+
+    let read_opts = ReadOptions::new();
+    let batch = WriteBatch::new();
+    batch
+        .delete_range(database, &read_opts, &[2], &[4])
+        .unwrap();
+    batch.put_u8(&[6], &[6]);
+    database.write(&write_opts, &batch).unwrap();
+
+    let keys: Vec<u8> = database.keys_iter(&read_opts).map(|k| k[0]).collect();
+    assert_eq!(keys, vec![1, 4, 5, 6]);
+}
+
+#[test]
+fn test_write_batch_str() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("writebatch_str");
+    let database = &Database::open(tmp.path(), &opts).unwrap();
+    let write_opts = WriteOptions::new();
+    database.put_str(&write_opts, b"stale", "old").unwrap();
+
+    let batch = WriteBatch::new();
+    let owned = String::from("owned");
+    batch.put_str("name", "h\u{e9}llo");
+    batch.put_str(&owned, "value");
+    batch.delete_str("stale");
+    database.write(&write_opts, &batch).unwrap();
+
+    let read_opts = ReadOptions::new();
+    assert_eq!(
+        database.get_str(&read_opts, b"name").unwrap(),
+        Some("h\u{e9}llo".to_string())
+    );
+    assert_eq!(
+        database.get_str(&read_opts, b"owned").unwrap(),
+        Some("value".to_string())
+    );
+    assert_eq!(database.get_str(&read_opts, b"stale").unwrap(), None);
+}
+
+#[test]
+fn test_buffered_writer() {
+    let mut opts = Options::new();
+    opts.create_if_missing = true;
+    let tmp = temp_dir("writebatch_buffered_writer");
+    let database = &Database::open(tmp.path(), &opts).unwrap();
+    let read_opts = ReadOptions::new();
+
+    let mut writer = database.buffered_writer(10);
+    for i in 1..=5u8 {
+        writer.put_u8(&[i], &[i]).unwrap();
+    }
+    assert_eq!(writer.pending(), 5);
+    assert_eq!(database.iter(&read_opts).count(), 0);
+
+    writer.flush().unwrap();
+    assert_eq!(writer.pending(), 0);
+    assert_eq!(database.iter(&read_opts).count(), 5);
+
+    // flushed automatically on the third operation and on drop
+    let mut writer = database.buffered_writer(3);
+    writer.delete_u8(&[1]).unwrap();
+    writer.delete_u8(&[2]).unwrap();
+    writer.put(&6, &[6]).unwrap();
+    assert_eq!(writer.pending(), 0);
+    assert_eq!(database.iter(&read_opts).count(), 4);
+    writer.delete_u8(&[3]).unwrap();
+    drop(writer);
+
+    let keys: Vec<Vec<u8>> = database.keys_iter(&read_opts).collect();
+    assert_eq!(keys, vec![vec![0, 0, 0, 6], vec![4], vec![5]]);
+}
+
+#[test]
+fn test_write_batch_conflict_detection() {
+    let batch = WriteBatch::with_conflict_detection();
+    batch.try_put_u8(b"a", b"1").unwrap();
+    batch.try_delete(&"b").unwrap();
+
+    let err = batch.try_put_u8(b"a", b"2").unwrap_err();
+    assert!(err.is_duplicate_key());
+    assert!(batch.try_delete_u8(b"b").unwrap_err().is_duplicate_key());
+    assert!(batch.try_put(&"b", b"3").unwrap_err().is_duplicate_key());
+    batch.try_put(&"c", b"3").unwrap();
+
+    // unchecked operations are tracked as well
+    batch.put_u8(b"d", b"4");
+    assert!(batch.try_delete_u8(b"d").unwrap_err().is_duplicate_key());
+
+    // the rejected operations were not added
+    let opts = Options {
+        create_if_missing: true,
+        ..Options::new()
+    };
+    let tmp = temp_dir("write_batch_conflict_detection");
+    let database = Database::open(tmp.path(), &opts).unwrap();
+    database.write(&WriteOptions::new(), &batch).unwrap();
+    let entries: Vec<_> = database.iter(&ReadOptions::new()).collect();
+    assert_eq!(
+        entries,
+        vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"c".to_vec(), b"3".to_vec()),
+            (b"d".to_vec(), b"4".to_vec())
+        ]
+    );
+
+    batch.clear();
+    batch.try_put_u8(b"a", b"5").unwrap();
+}
+
+#[test]
+#[should_panic(expected = "duplicate key added unchecked to a batch detecting conflicts")]
+fn test_write_batch_conflict_detection_unchecked_duplicate() {
+    let batch = WriteBatch::with_conflict_detection();
+    batch.try_put_u8(b"a", b"1").unwrap();
+    batch.delete_u8(b"a");
+}
+
+#[test]
+fn test_write_batch_without_conflict_detection() {
+    let batch = WriteBatch::new();
+    batch.try_put_u8(b"a", b"1").unwrap();
+    batch.try_put_u8(b"a", b"2").unwrap();
+    batch.try_delete_u8(b"a").unwrap();
+}